@@ -0,0 +1,12 @@
+#version 400
+
+uniform sampler2D font_atlas;
+
+in vec2 v_tex_coords;
+in vec4 v_color;
+
+out vec4 f_color;
+
+void main() {
+    f_color = vec4(v_color.rgb, v_color.a * texture(font_atlas, v_tex_coords).a);
+}
@@ -0,0 +1,17 @@
+#version 400
+
+uniform vec2 screen_size;
+
+in vec2 position;
+in vec2 tex_coords;
+in vec4 color;
+
+out vec2 v_tex_coords;
+out vec4 v_color;
+
+void main() {
+    vec2 normalized = position / screen_size * 2.0 - 1.0;
+    gl_Position = vec4(normalized.x, -normalized.y, 0.0, 1.0);
+    v_tex_coords = tex_coords;
+    v_color = color;
+}
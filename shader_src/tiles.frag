@@ -1,17 +1,61 @@
 #version 400
 
+// shape ids, see game_data::Shape
+const int SQUARE = 0;
+const int CIRCLE = 1;
+const int TRIANGLE = 2;
+const int DIAMOND = 3;
+const int RING = 4;
+const int CROSS = 5;
+const int ARROW_NORTH = 6;
+const int ARROW_EAST = 7;
+const int ARROW_SOUTH = 8;
+const int ARROW_WEST = 9;
+
 in vec4 gf_color;
 in vec3 gf_position;
-in float gf_radius2;
+flat in int gf_shape;
 in vec3 gf_center;
 
 out vec4 f_color;
 
+// p is relative to the center of the cell, with the arrow pointing towards positive y
+bool in_arrow(vec2 p) {
+    if (p.y >= 0.0) {
+        return abs(p.x) <= 0.8 * (0.5 - p.y);
+    }
+    return abs(p.x) <= 0.12;
+}
+
+bool in_shape(vec2 p) {
+    float d2 = p.x * p.x + p.y * p.y;
+    switch (gf_shape) {
+    case CIRCLE:
+        return d2 <= 0.25;
+    case TRIANGLE:
+        return abs(p.x) <= 0.5 * (0.5 - p.y);
+    case DIAMOND:
+        return abs(p.x) + abs(p.y) <= 0.5;
+    case RING:
+        return d2 <= 0.25 && d2 >= 0.09;
+    case CROSS:
+        return abs(p.x - p.y) <= 0.15 || abs(p.x + p.y) <= 0.15;
+    case ARROW_NORTH:
+        return in_arrow(p);
+    case ARROW_EAST:
+        return in_arrow(vec2(-p.y, p.x));
+    case ARROW_SOUTH:
+        return in_arrow(vec2(-p.x, -p.y));
+    case ARROW_WEST:
+        return in_arrow(vec2(p.y, -p.x));
+    default:
+        return true;
+    }
+}
+
 void main() {
-    f_color = gf_color;
-    vec2 delta = gf_position.xy - gf_center.xy;
-    float d2 = delta.x * delta.x + delta.y * delta.y;
-    if (d2 > gf_radius2) {
+    if (!in_shape(gf_position.xy - gf_center.xy)) {
         discard;
     }
+    f_color = gf_color;
 }
\ No newline at end of file
@@ -2,14 +2,14 @@
 
 in vec3 position;
 in vec4 color;
-in float radius2;
+in int shape;
 
 out vec4 vg_color;
 out vec3 vg_position;
-out float vg_radius2;
+flat out int vg_shape;
 
 void main() {
     vg_position = position;
     vg_color = color;
-    vg_radius2 = radius2;
-}
+    vg_shape = shape;
+}
\ No newline at end of file
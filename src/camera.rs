@@ -0,0 +1,45 @@
+use cgmath::Matrix4;
+use cgmath::Vector2;
+use cgmath::Vector3;
+
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 200.0;
+
+/// Zooming and panning on top of fitting the whole board into the window.
+///
+/// The camera works in normalized device coordinates, so it does not have to change when the
+/// window is resized.
+pub struct Camera {
+    zoom: f32,
+    offset: Vector2<f32>,
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            zoom: 1.0,
+            offset: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// Applied to normalized device coordinates after the fit transformation.
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::new(self.offset.x, self.offset.y, 0.0))
+            * Matrix4::from_nonuniform_scale(self.zoom, self.zoom, 1.0)
+    }
+
+    /// Zooms by a factor, keeping the given point of the screen in place.
+    pub fn zoom_at(&mut self, factor: f32, point: Vector2<f32>) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = point - (point - self.offset) * (zoom / self.zoom);
+        self.zoom = zoom;
+    }
+
+    pub fn pan(&mut self, delta: Vector2<f32>) {
+        self.offset += delta;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Camera::new();
+    }
+}
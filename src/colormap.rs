@@ -0,0 +1,77 @@
+use cgmath::Vector3;
+
+/// Maps values between 0 and 1 to colors, for drawing heat maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    Viridis,
+    RedBlue,
+    Grayscale,
+}
+
+/// The names of the colormaps in insight files.
+const COLORMAP_NAMES: [(&str, Colormap); 3] = [
+    ("viridis", Colormap::Viridis),
+    ("redblue", Colormap::RedBlue),
+    ("grayscale", Colormap::Grayscale),
+];
+
+/// Evenly spaced samples of matplotlib's viridis.
+const VIRIDIS: [[f32; 3]; 9] = [
+    [0.267, 0.005, 0.329],
+    [0.278, 0.173, 0.478],
+    [0.231, 0.317, 0.545],
+    [0.173, 0.443, 0.557],
+    [0.129, 0.565, 0.553],
+    [0.153, 0.678, 0.506],
+    [0.361, 0.784, 0.388],
+    [0.667, 0.863, 0.196],
+    [0.992, 0.906, 0.145],
+];
+
+const RED_BLUE: [[f32; 3]; 3] = [
+    [0.230, 0.299, 0.754],
+    [0.970, 0.970, 0.970],
+    [0.706, 0.016, 0.150],
+];
+
+const GRAYSCALE: [[f32; 3]; 2] = [
+    [0.0, 0.0, 0.0],
+    [1.0, 1.0, 1.0],
+];
+
+impl Colormap {
+    /// The name used for the colormap in insight files.
+    pub fn name(&self) -> &'static str {
+        COLORMAP_NAMES.iter().find(|&&(_, colormap)| colormap == *self).unwrap().0
+    }
+
+    pub fn from_name(name: &str) -> Option<Colormap> {
+        COLORMAP_NAMES.iter()
+            .find(|&&(colormap_name, _)| name == colormap_name)
+            .map(|&(_, colormap)| colormap)
+    }
+
+    /// The names of all colormaps, for error messages.
+    pub fn names() -> String {
+        COLORMAP_NAMES.iter()
+            .map(|&(name, _)| name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The color at a position between 0 and 1, positions outside being clamped.
+    pub fn color(&self, position: f32) -> Vector3<f32> {
+        let samples: &[[f32; 3]] = match *self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::RedBlue => &RED_BLUE,
+            Colormap::Grayscale => &GRAYSCALE,
+        };
+        let position = if position.is_nan() { 0.0 } else { position.clamp(0.0, 1.0) };
+        let scaled = position * (samples.len() - 1) as f32;
+        let index = (scaled as usize).min(samples.len() - 2);
+        let fraction = scaled - index as f32;
+        let low = Vector3::from(samples[index]);
+        let high = Vector3::from(samples[index + 1]);
+        low + (high - low) * fraction
+    }
+}
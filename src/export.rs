@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::ops::RangeInclusive;
+
+use gif;
+
+use game_data::GameData;
+use render::Renderer;
+
+/// Renders a range of turns into an animated GIF that loops forever.
+pub fn export_gif(renderer: &mut Renderer, game_data: &GameData, turns: RangeInclusive<u32>,
+                  fps: f32, file_name: &str) -> io::Result<()> {
+    encode_gif(renderer, game_data, turns, fps, file_name).map_err(|error| {
+        io::Error::new(error.kind(), format!("could not write {}: {}", file_name, error))
+    })
+}
+
+/// Renders a range of turns into one PNG file per turn, named by inserting the turn number
+/// in front of the extension of the given file name.
+pub fn export_png_sequence(renderer: &mut Renderer, game_data: &GameData,
+                           turns: RangeInclusive<u32>, file_name: &str) -> io::Result<()> {
+    for turn in turns {
+        renderer.render(game_data, turn);
+        renderer.write_png(&sequence_file_name(file_name, turn))?;
+    }
+    Ok(())
+}
+
+/// Turns fight.png into fight_0042.png for turn 42.
+fn sequence_file_name(file_name: &str, turn: u32) -> String {
+    let (stem, extension) = match file_name.rfind('.') {
+        Some(dot) if !file_name[dot..].contains('/') => file_name.split_at(dot),
+        _ => (file_name, ""),
+    };
+    format!("{}_{:04}{}", stem, turn, extension)
+}
+
+fn encode_gif(renderer: &mut Renderer, game_data: &GameData, turns: RangeInclusive<u32>,
+              fps: f32, file_name: &str) -> io::Result<()> {
+    let width = gif_dimension(renderer.width())?;
+    let height = gif_dimension(renderer.height())?;
+    // GIF delays are given in hundredths of a second
+    let delay = (100.0 / fps).round().clamp(1.0, u16::MAX as f32) as u16;
+
+    let file = File::create(file_name)?;
+    let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &[])
+        .map_err(io::Error::other)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+    for turn in turns {
+        renderer.render(game_data, turn);
+        let mut frame = gif::Frame::from_rgb_speed(width, height, &renderer.rgb(), 10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+fn gif_dimension(size: u32) -> io::Result<u16> {
+    if size > u16::MAX as u32 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("GIF images cannot be larger than {} pixels", u16::MAX),
+        ));
+    }
+    Ok(size as u16)
+}
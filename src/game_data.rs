@@ -1,48 +1,258 @@
 use std::io::BufReader;
 use std::io::BufRead;
+use std::io::Seek;
+use std::io::SeekFrom;
 use std::fs::File;
-use std::slice;
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::BTreeMap;
+use std::sync::mpsc::TryRecvError;
 
 use cgmath::Vector3;
 use cgmath::Vector4;
 
-#[derive(Clone, Copy, Debug)]
+use parser::ParseError;
+use parser::LineReader;
+use parser::Words;
+use scanner;
+use scanner::Scanner;
+use scanner::ScannedTurn;
+use scanner::TurnSpan;
+use turn_cache::TurnCache;
+use colormap::Colormap;
+
+const TURN_CACHE_SIZE: usize = 64;
+
+/// The shape of a tile.
+///
+/// The discriminants are the shape ids used by the tile shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Shape {
-    Square,
-    Circle,
+    Square = 0,
+    Circle = 1,
+    Triangle = 2,
+    Diamond = 3,
+    Ring = 4,
+    Cross = 5,
+    ArrowNorth = 6,
+    ArrowEast = 7,
+    ArrowSouth = 8,
+    ArrowWest = 9,
+}
+
+/// The names of the shapes in insight files, each with a short alias.
+const SHAPE_NAMES: [(&str, &str, Shape); 10] = [
+    ("square", "s", Shape::Square),
+    ("circle", "c", Shape::Circle),
+    ("triangle", "t", Shape::Triangle),
+    ("diamond", "d", Shape::Diamond),
+    ("ring", "r", Shape::Ring),
+    ("cross", "x", Shape::Cross),
+    ("arrow_north", "^", Shape::ArrowNorth),
+    ("arrow_east", ">", Shape::ArrowEast),
+    ("arrow_south", "v", Shape::ArrowSouth),
+    ("arrow_west", "<", Shape::ArrowWest),
+];
+
+impl Shape {
+    /// The name used for the shape in insight files.
+    pub fn name(&self) -> &'static str {
+        SHAPE_NAMES.iter().find(|&&(_, _, shape)| shape == *self).unwrap().0
+    }
+
+    /// Looks up a shape by its name or alias.
+    pub fn from_name(name: &str) -> Option<Shape> {
+        SHAPE_NAMES.iter()
+            .find(|&&(long, short, _)| name == long || name == short)
+            .map(|&(_, _, shape)| shape)
+    }
+
+    /// Whether a point belongs to the shape, relative to the center of its cell with y
+    /// pointing up, the same way the tile shader decides it.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let d2 = x * x + y * y;
+        match *self {
+            Shape::Square => true,
+            Shape::Circle => d2 <= 0.25,
+            Shape::Triangle => x.abs() <= 0.5 * (0.5 - y),
+            Shape::Diamond => x.abs() + y.abs() <= 0.5,
+            Shape::Ring => (0.09..=0.25).contains(&d2),
+            Shape::Cross => (x - y).abs() <= 0.15 || (x + y).abs() <= 0.15,
+            Shape::ArrowNorth => in_arrow(x, y),
+            Shape::ArrowEast => in_arrow(-y, x),
+            Shape::ArrowSouth => in_arrow(-x, -y),
+            Shape::ArrowWest => in_arrow(y, -x),
+        }
+    }
+}
+
+/// Whether a point belongs to an arrow pointing towards positive y.
+fn in_arrow(x: f32, y: f32) -> bool {
+    if y >= 0.0 {
+        x.abs() <= 0.8 * (0.5 - y)
+    } else {
+        x.abs() <= 0.12
+    }
 }
 
 struct Index {
     position_index: usize,
     palette_index: usize,
+    entity: Option<u32>,
 }
 
+#[derive(Clone)]
 struct PaletteEntry {
+    symbol: String,
     shape: Shape,
     color: Vector4<f32>,
-    layer: u8,
+    layer: u32,
 }
 
 pub struct Tile {
     pub row: u32,
     pub col: u32,
-    pub layer: u8,
+    pub layer: u32,
     pub shape: Shape,
     pub color: Vector4<f32>,
+    /// The id of the entity shown by the tile, if it is one.
+    pub entity: Option<u32>,
+}
+
+/// Where an entity was in a turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Waypoint {
+    pub turn: u32,
+    pub row: u32,
+    pub col: u32,
 }
 
+#[derive(Clone)]
 pub struct Line {
     pub r1: u32,
     pub c1: u32,
     pub r2: u32,
     pub c2: u32,
     pub color: Vector4<f32>,
-    pub layer: u8,
+    pub layer: u32,
+}
+
+/// Free text attached to a turn, optionally referring to a cell in a layer.
+#[derive(Clone)]
+pub struct Message {
+    pub text: String,
+    pub cell: Option<(u32, u32)>,
+    pub layer: Option<u32>,
+}
+
+/// A short text or number written onto a cell.
+#[derive(Clone)]
+pub struct Label {
+    pub row: u32,
+    pub col: u32,
+    pub layer: u32,
+    pub text: String,
+}
+
+/// One value per cell of a layer.
+pub struct HeatMap {
+    pub layer: u32,
+    num_cols: u32,
+    values: Vec<f32>,
+}
+
+impl HeatMap {
+    /// The value of a cell, or None if the cell has none.
+    pub fn value(&self, row: u32, col: u32) -> Option<f32> {
+        let value = *self.values.get((row * self.num_cols + col) as usize)?;
+        if value.is_nan() { None } else { Some(value) }
+    }
+
+    /// The values of all cells row by row, NaN marking cells without a value.
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    /// The smallest and the largest value, if there are any values.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        self.values.iter()
+            .filter(|value| !value.is_nan())
+            .fold(None, |range, &value| match range {
+                Some((min, max)) => Some((value.min(min), value.max(max))),
+                None => Some((value, value)),
+            })
+    }
+}
+
+/// How the values of the heat maps of a layer are mapped to colors.
+#[derive(Clone, Copy)]
+pub struct HeatScale {
+    pub colormap: Colormap,
+    /// The values mapped to both ends of the colormap. Without a range, each heat map is
+    /// scaled to its own range.
+    pub range: Option<(f32, f32)>,
+}
+
+impl HeatScale {
+    /// The values mapped to the ends of the colormap for a heat map.
+    pub fn bounds(&self, heat_map: &HeatMap) -> (f32, f32) {
+        self.range.or_else(|| heat_map.range()).unwrap_or((0.0, 1.0))
+    }
+
+    pub fn color(&self, value: f32, bounds: (f32, f32)) -> Vector3<f32> {
+        let (min, max) = bounds;
+        let position = if max > min { (value - min) / (max - min) } else { 0.5 };
+        self.colormap.color(position)
+    }
+}
+
+impl Default for HeatScale {
+    fn default() -> HeatScale {
+        HeatScale {
+            colormap: Colormap::Viridis,
+            range: None,
+        }
+    }
+}
+
+/// Everything in front of the first turn.
+#[derive(Clone)]
+pub struct Header {
+    num_rows: u32,
+    num_cols: u32,
+    palette: Vec<PaletteEntry>,
+    palette_map: HashMap<String, usize>,
+    long_symbols: bool,
+    layer_names: BTreeMap<u32, String>,
+    heat_scales: HashMap<u32, HeatScale>,
+    wrap: bool,
+    background_color: Vector3<f32>,
+}
+
+#[derive(Default)]
+pub struct Turn {
+    tiles: Vec<Index>,
+    lines: Vec<Line>,
+    messages: Vec<Message>,
+    labels: Vec<Label>,
+    heat_maps: Vec<Arc<HeatMap>>,
+}
+
+/// How the block of a turn was terminated.
+#[derive(PartialEq, Eq)]
+pub enum TurnEnd {
+    NextTurn,
+    End,
+    EndOfFile,
 }
 
 pub struct TileIterator<'a> {
-    inner: slice::Iter<'a, Index>,
+    turn: Rc<Turn>,
+    index: usize,
     palette: &'a Vec<PaletteEntry>,
     num_cols: u32,
 }
@@ -50,80 +260,497 @@ pub struct TileIterator<'a> {
 impl<'a> Iterator for TileIterator<'a> {
     type Item = Tile;
     fn next(&mut self) -> Option<Tile> {
-        self.inner.next().map(|index| {
-            let palette_entry = &self.palette[index.palette_index];
-            Tile {
-                row: index.position_index as u32 / self.num_cols,
-                col: index.position_index as u32 % self.num_cols,
-                shape: palette_entry.shape,
-                color: palette_entry.color,
-                layer: palette_entry.layer,
-            }
-        })
+        let index = self.turn.tiles.get(self.index)?;
+        self.index += 1;
+        let palette_entry = &self.palette[index.palette_index];
+        Some(palette_entry.tile(index, self.num_cols))
+    }
+}
+
+impl PaletteEntry {
+    fn tile(&self, index: &Index, num_cols: u32) -> Tile {
+        Tile {
+            row: index.position_index as u32 / num_cols,
+            col: index.position_index as u32 % num_cols,
+            shape: self.shape,
+            color: self.color,
+            layer: self.layer,
+            entity: index.entity,
+        }
+    }
+}
+
+pub struct LineIterator {
+    turn: Rc<Turn>,
+    index: usize,
+}
+
+impl Iterator for LineIterator {
+    type Item = Line;
+    fn next(&mut self) -> Option<Line> {
+        let line = self.turn.lines.get(self.index)?.clone();
+        self.index += 1;
+        Some(line)
+    }
+}
+
+pub struct MessageIterator {
+    turn: Rc<Turn>,
+    index: usize,
+}
+
+impl Iterator for MessageIterator {
+    type Item = Message;
+    fn next(&mut self) -> Option<Message> {
+        let message = self.turn.messages.get(self.index)?.clone();
+        self.index += 1;
+        Some(message)
     }
 }
 
+pub struct LabelIterator {
+    turn: Rc<Turn>,
+    index: usize,
+}
+
+impl Iterator for LabelIterator {
+    type Item = Label;
+    fn next(&mut self) -> Option<Label> {
+        let label = self.turn.labels.get(self.index)?.clone();
+        self.index += 1;
+        Some(label)
+    }
+}
+
+pub struct HeatMapIterator {
+    turn: Rc<Turn>,
+    index: usize,
+}
+
+impl Iterator for HeatMapIterator {
+    type Item = Arc<HeatMap>;
+    fn next(&mut self) -> Option<Arc<HeatMap>> {
+        let heat_map = self.turn.heat_maps.get(self.index)?.clone();
+        self.index += 1;
+        Some(heat_map)
+    }
+}
+
+/// Where the text of indexed turns is read from.
+enum Storage {
+    File(RefCell<BufReader<File>>),
+    Memory(Vec<u8>),
+}
+
+/// The contents of an insight file.
+///
+/// Loading only reads the header. The turns are indexed by a background scanner and parsed on
+/// demand, keeping only the most recently used ones in memory.
 pub struct GameData {
-    num_rows: u32,
-    num_cols: u32,
-    palette: Vec<PaletteEntry>,
-    tiles: Vec<Vec<Index>>,
-    lines: Vec<Vec<Line>>,
-    background_color: Vector3<f32>,
+    header: Header,
+    file_name: String,
+    storage: Storage,
+    scanner: Option<Scanner>,
+    turn_spans: Vec<TurnSpan>,
+    turn_cache: RefCell<TurnCache<Turn>>,
+    /// The error of a turn that could not be parsed again, to be returned by the next update.
+    read_error: RefCell<Option<ParseError>>,
+    trajectories: HashMap<u32, Vec<Waypoint>>,
 }
 
 impl GameData {
-    pub fn load(file_name: &str) -> GameData {
-        let file = File::open(file_name).unwrap();
-        let mut reader = BufReader::new(file);
-        let mut line_buffer = String::new();
+    /// Opens an insight file.
+    ///
+    /// When following, the file is expected to still be written to and turns are added as soon
+    /// as they are complete.
+    pub fn load(file_name: &str, follow: bool) -> Result<GameData, ParseError> {
+        let open = || File::open(file_name).map_err(|error| ParseError::io(file_name, 0, error));
+        let mut reader = LineReader::new(BufReader::new(open()?), file_name);
+        reader.set_follow(follow);
+        let header = Header::parse(&mut reader)?;
+        let scanner = scanner::spawn(header.clone(), reader);
+        Ok(GameData {
+            header,
+            file_name: file_name.to_string(),
+            storage: Storage::File(RefCell::new(BufReader::new(open()?))),
+            scanner: Some(scanner),
+            turn_spans: Vec::new(),
+            turn_cache: RefCell::new(TurnCache::new(TURN_CACHE_SIZE)),
+            read_error: RefCell::new(None),
+            trajectories: HashMap::new(),
+        })
+    }
+
+    /// Reads insight data from a stream like stdin or a pipe.
+    ///
+    /// Since the stream cannot be read twice, the text of every turn is kept in memory. The
+    /// name is only used in error messages.
+    pub fn from_reader<R>(reader: R, name: &str) -> Result<GameData, ParseError>
+        where R: BufRead + Send + 'static
+    {
+        let mut reader = LineReader::new(reader, name);
+        let header = Header::parse(&mut reader)?;
+        reader.set_recording(true);
+        let scanner = scanner::spawn(header.clone(), reader);
+        Ok(GameData {
+            header,
+            file_name: name.to_string(),
+            storage: Storage::Memory(Vec::new()),
+            scanner: Some(scanner),
+            turn_spans: Vec::new(),
+            turn_cache: RefCell::new(TurnCache::new(TURN_CACHE_SIZE)),
+            read_error: RefCell::new(None),
+            trajectories: HashMap::new(),
+        })
+    }
+
+    /// Adds the turns the scanner has indexed since the last call.
+    ///
+    /// Returns the errors of the scanner, and of turns that could not be parsed again because
+    /// the file changed after they were indexed.
+    ///
+    /// Returns whether there are new turns.
+    pub fn update(&mut self) -> Result<bool, ParseError> {
+        if let Some(error) = self.read_error.get_mut().take() {
+            return Err(error);
+        }
+        let num_turns = self.turn_spans.len();
+        if let Some(scanner) = self.scanner.take() {
+            loop {
+                match scanner.try_recv() {
+                    Ok(scanned_turn) => self.add_turn(scanned_turn?),
+                    Err(TryRecvError::Empty) => {
+                        self.scanner = Some(scanner);
+                        break;
+                    },
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+        }
+        Ok(self.turn_spans.len() > num_turns)
+    }
+
+    /// Blocks until the scanner has indexed the whole input.
+    pub fn wait(&mut self) -> Result<(), ParseError> {
+        if let Some(error) = self.read_error.get_mut().take() {
+            return Err(error);
+        }
+        if let Some(scanner) = self.scanner.take() {
+            for scanned_turn in scanner {
+                self.add_turn(scanned_turn?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the whole input has been indexed.
+    pub fn is_complete(&self) -> bool {
+        self.scanner.is_none()
+    }
+
+    fn add_turn(&mut self, scanned_turn: ScannedTurn) {
+        let ScannedTurn { mut span, text, turn, waypoints } = scanned_turn;
+        // the turn has just been parsed, so it does not need to be read again to be shown
+        let index = self.turn_spans.len() as u32;
+        self.turn_cache.get_mut().insert(index, Rc::new(turn));
+        if let (&mut Storage::Memory(ref mut memory), Some(text)) = (&mut self.storage, text) {
+            span.position = memory.len() as u64;
+            memory.extend_from_slice(&text);
+        }
+        self.turn_spans.push(span);
+        for (id, waypoint) in waypoints {
+            self.trajectories.entry(id).or_default().push(waypoint);
+        }
+    }
+
+    /// The name of the file or stream the game is read from.
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn num_turns(&self) -> u32 {
+        self.turn_spans.len() as u32
+    }
+
+    /// Whether a turn has messages or labels, which is known without parsing it again.
+    pub fn is_annotated(&self, turn: u32) -> bool {
+        self.turn_spans[turn as usize].annotated
+    }
+
+    pub fn num_rows(&self) -> u32 {
+        self.header.num_rows
+    }
+
+    pub fn num_cols(&self) -> u32 {
+        self.header.num_cols
+    }
+
+    pub fn background_color(&self) -> Vector3<f32> {
+        self.header.background_color
+    }
+
+    /// Whether the edges of the board wrap around, making it a torus.
+    pub fn wraps(&self) -> bool {
+        self.header.wrap
+    }
+
+    /// Overrides whether the board wraps around, which is otherwise declared in the header.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.header.wrap = wrap;
+    }
+
+    /// The name a layer was declared with in the header, if any.
+    pub fn layer_name(&self, layer: u32) -> Option<&str> {
+        self.header.layer_names.get(&layer).map(|name| name.as_str())
+    }
+
+    /// How the heat maps of a layer are colored.
+    pub fn heat_scale(&self, layer: u32) -> HeatScale {
+        self.header.heat_scales.get(&layer).cloned().unwrap_or_default()
+    }
 
+    pub fn tiles(&self, turn: u32) -> TileIterator<'_> {
+        TileIterator {
+            turn: self.turn(turn),
+            index: 0,
+            palette: &self.header.palette,
+            num_cols: self.header.num_cols,
+        }
+    }
+
+    /// The tiles of a turn together with their palette symbols.
+    pub fn tiles_with_symbols(&self, turn: u32) -> Vec<(&str, Tile)> {
+        let num_cols = self.header.num_cols;
+        self.turn(turn).tiles.iter()
+            .map(|index| {
+                let palette_entry = &self.header.palette[index.palette_index];
+                (palette_entry.symbol.as_str(), palette_entry.tile(index, num_cols))
+            })
+            .collect()
+    }
+
+    /// The tiles in a cell together with their palette symbols.
+    pub fn cell_tiles(&self, turn: u32, row: u32, col: u32) -> Vec<(&str, Tile)> {
+        self.tiles_with_symbols(turn).into_iter()
+            .filter(|(_, tile)| tile.row == row && tile.col == col)
+            .collect()
+    }
+
+    /// The tiles of a turn that show entities.
+    pub fn entities(&self, turn: u32) -> Vec<Tile> {
+        self.tiles(turn).filter(|tile| tile.entity.is_some()).collect()
+    }
+
+    /// The ids of all entities in the turns indexed so far, in ascending order.
+    pub fn entity_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.trajectories.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Where an entity was in every turn indexed so far that it appears in, in the order of
+    /// the turns. This is known without parsing the turns again.
+    pub fn trajectory(&self, id: u32) -> &[Waypoint] {
+        self.trajectories.get(&id).map_or(&[], |trajectory| trajectory.as_slice())
+    }
+
+    /// Where an entity was in a turn, if it was there at all.
+    pub fn entity_position(&self, id: u32, turn: u32) -> Option<Waypoint> {
+        let trajectory = self.trajectory(id);
+        trajectory.binary_search_by_key(&turn, |waypoint| waypoint.turn).ok()
+            .map(|index| trajectory[index])
+    }
+
+    pub fn lines(&self, turn: u32) -> LineIterator {
+        LineIterator {
+            turn: self.turn(turn),
+            index: 0,
+        }
+    }
+
+    pub fn messages(&self, turn: u32) -> MessageIterator {
+        MessageIterator {
+            turn: self.turn(turn),
+            index: 0,
+        }
+    }
+
+    pub fn labels(&self, turn: u32) -> LabelIterator {
+        LabelIterator {
+            turn: self.turn(turn),
+            index: 0,
+        }
+    }
+
+    pub fn heat_maps(&self, turn: u32) -> HeatMapIterator {
+        HeatMapIterator {
+            turn: self.turn(turn),
+            index: 0,
+        }
+    }
+
+    fn turn(&self, turn: u32) -> Rc<Turn> {
+        let mut turn_cache = self.turn_cache.borrow_mut();
+        if let Some(data) = turn_cache.get(turn) {
+            return data;
+        }
+        match self.read_turn(turn) {
+            Ok(data) => {
+                let data = Rc::new(data);
+                turn_cache.insert(turn, data.clone());
+                data
+            },
+            Err(error) => {
+                // the file has changed since it was indexed, for example because a bot was run
+                // again, so the turn is shown empty until the error is picked up by an update
+                let mut read_error = self.read_error.borrow_mut();
+                if read_error.is_none() {
+                    *read_error = Some(error);
+                }
+                Rc::new(Turn::default())
+            },
+        }
+    }
+
+    fn read_turn(&self, turn: u32) -> Result<Turn, ParseError> {
+        let span = &self.turn_spans[turn as usize];
+        let (data, _) = match self.storage {
+            Storage::File(ref file) => {
+                let mut file = file.borrow_mut();
+                file.seek(SeekFrom::Start(span.position))
+                    .map_err(|error| ParseError::io(&self.file_name, span.line_number, error))?;
+                let mut reader = LineReader::with_position(
+                    &mut *file,
+                    &self.file_name,
+                    span.line_number,
+                    span.position,
+                );
+                Turn::parse(&self.header, &mut reader)?
+            },
+            Storage::Memory(ref memory) => {
+                let mut reader = LineReader::with_position(
+                    &memory[span.position as usize..],
+                    &self.file_name,
+                    span.line_number,
+                    span.position,
+                );
+                Turn::parse(&self.header, &mut reader)?
+            },
+        };
+        Ok(data)
+    }
+}
+
+impl Header {
+    /// Parses the header up to and including the `turn` line of the first turn.
+    pub fn parse<R: BufRead>(reader: &mut LineReader<R>) -> Result<Header, ParseError> {
         // read board size
         let num_rows;
         let num_cols;
         {
-            line_buffer.clear();
-            reader.read_line(&mut line_buffer).unwrap();
-            let mut words = line_buffer.split_whitespace();
-            num_rows = words.next().unwrap().parse::<u32>().unwrap();
-            num_cols = words.next().unwrap().parse::<u32>().unwrap();
+            reader.expect_line("board size")?;
+            let mut words = reader.words();
+            num_rows = words.parse::<u32>("number of rows", "board size")?;
+            num_cols = words.parse::<u32>("number of columns", "board size")?;
         }
 
         // read background color
         let background_color;
         {
-            line_buffer.clear();
-            reader.read_line(&mut line_buffer).unwrap();
-            let mut words = line_buffer.split_whitespace();
-            let red   = words.next().unwrap().parse::<f32>().unwrap();
-            let green = words.next().unwrap().parse::<f32>().unwrap();
-            let blue  = words.next().unwrap().parse::<f32>().unwrap();
+            reader.expect_line("background color")?;
+            let mut words = reader.words();
+            let red   = words.parse::<f32>("red", "background color")?;
+            let green = words.parse::<f32>("green", "background color")?;
+            let blue  = words.parse::<f32>("blue", "background color")?;
             background_color = Vector3::new(red, green, blue);
         }
 
-        // read palette
+        // read palette and layer names
         let mut palette_map = HashMap::new();
         let mut palette = Vec::new();
+        let mut layer_names = BTreeMap::new();
+        let mut heat_scales = HashMap::new();
+        let mut wrap = false;
+        let mut long_symbols = false;
         loop {
-            line_buffer.clear();
-            reader.read_line(&mut line_buffer).unwrap();
-            let mut words = line_buffer.split_whitespace();
-            match words.next().unwrap() {
+            reader.expect_line("palette entry, layer or `turn`")?;
+            let mut words = reader.words();
+            let expected = "symbol, `layer`, `heat`, `symbols`, `wrap` or `turn`";
+            match words.next_word(expected, "palette entry")? {
                 "turn" => break,
+                "symbols" => {
+                    if !palette.is_empty() {
+                        return Err(words.error("symbols", "symbol format before palette entries"));
+                    }
+                    long_symbols = match words.next_word("symbol format", "header")? {
+                        "short" => false,
+                        "long" => true,
+                        word => return Err(words.error(word, "`short` or `long` symbols")),
+                    };
+                },
+                "wrap" => wrap = true,
+                "layer" => {
+                    let layer = words.parse::<u32>("layer", "layer declaration")?;
+                    let name = words.quoted("name", "layer declaration")?;
+                    layer_names.insert(layer, name);
+                },
+                "heat" => {
+                    let layer = words.parse::<u32>("layer", "heat scale")?;
+                    let colormap_name = words.next_word("colormap", "heat scale")?;
+                    let colormap = match Colormap::from_name(colormap_name) {
+                        Some(colormap) => colormap,
+                        None => return Err(words.error(colormap_name, &format!(
+                            "colormap in heat scale ({})",
+                            Colormap::names(),
+                        ))),
+                    };
+                    let range = match words.try_next() {
+                        Some(word) => {
+                            let min = word.parse::<f32>().map_err(|_| {
+                                words.error(word, "minimum as f32 in heat scale")
+                            })?;
+                            let max = words.parse::<f32>("maximum", "heat scale")?;
+                            if min.partial_cmp(&max) != Some(Ordering::Less) {
+                                let expected = format!("maximum above {} in heat scale", min);
+                                return Err(words.error(&max.to_string(), &expected));
+                            }
+                            Some((min, max))
+                        },
+                        None => None,
+                    };
+                    heat_scales.insert(layer, HeatScale {
+                        colormap,
+                        range,
+                    });
+                },
                 word => {
-                    let character = word.chars().nth(0).unwrap();
-                    let shape = match words.next().unwrap().chars().nth(0).unwrap() {
-                        'c' => Shape::Circle,
-                        _ => Shape::Square,
+                    let symbol = if long_symbols {
+                        if word == "." || word.contains(',') {
+                            return Err(words.error(word, "symbol without '.' and ','"));
+                        }
+                        word.to_string()
+                    } else {
+                        word.chars().nth(0).unwrap().to_string()
+                    };
+                    let shape_name = words.next_word("shape", "palette entry")?;
+                    let shape = match Shape::from_name(shape_name) {
+                        Some(shape) => shape,
+                        None => return Err(words.error(shape_name, &format!(
+                            "shape in palette entry ({})",
+                            SHAPE_NAMES.iter()
+                                .map(|&(long, short, _)| format!("{} or {}", long, short))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        ))),
                     };
-                    let red   = words.next().unwrap().parse::<f32>().unwrap();
-                    let green = words.next().unwrap().parse::<f32>().unwrap();
-                    let blue  = words.next().unwrap().parse::<f32>().unwrap();
-                    let alpha = words.next().unwrap().parse::<f32>().unwrap();
-                    let layer = words.next().unwrap().parse::<u8>().unwrap();
-                    assert!(layer < 10);
-                    palette_map.insert(character, palette.len());
+                    let red   = words.parse::<f32>("red", "palette entry")?;
+                    let green = words.parse::<f32>("green", "palette entry")?;
+                    let blue  = words.parse::<f32>("blue", "palette entry")?;
+                    let alpha = words.parse::<f32>("alpha", "palette entry")?;
+                    let layer = words.parse::<u32>("layer", "palette entry")?;
+                    palette_map.insert(symbol.clone(), palette.len());
                     palette.push(PaletteEntry {
+                        symbol,
                         shape,
                         color: Vector4::new(red, green, blue, alpha),
                         layer,
@@ -132,112 +759,508 @@ impl GameData {
             }
         }
 
+        Ok(Header {
+            num_rows,
+            num_cols,
+            palette,
+            palette_map,
+            long_symbols,
+            layer_names,
+            heat_scales,
+            wrap,
+            background_color,
+        })
+    }
+
+    fn tile_index(&self, words: &Words, row: u32, col: u32, symbol: &str, position_index: usize)
+                  -> Result<Index, ParseError> {
+        match self.palette_map.get(symbol) {
+            Some(&palette_index) => Ok(Index {
+                position_index,
+                palette_index,
+                entity: None,
+            }),
+            None => Err(words.error(
+                symbol,
+                &format!("palette symbol in cell ({}, {})", row, col),
+            )),
+        }
+    }
+}
+
+impl Turn {
+    pub fn is_annotated(&self) -> bool {
+        !self.messages.is_empty() || !self.labels.is_empty()
+    }
+
+    /// The positions of the entities in the turn, given its number, along with their ids.
+    pub fn waypoints(&self, header: &Header, turn: u32) -> Vec<(u32, Waypoint)> {
+        let num_cols = header.num_cols;
+        self.tiles.iter()
+            .filter_map(|index| {
+                let waypoint = Waypoint {
+                    turn,
+                    row: index.position_index as u32 / num_cols,
+                    col: index.position_index as u32 % num_cols,
+                };
+                index.entity.map(|id| (id, waypoint))
+            })
+            .collect()
+    }
+
+    /// Parses a turn, starting after its `turn` line and consuming the line that ends it.
+    pub fn parse<R: BufRead>(header: &Header, reader: &mut LineReader<R>)
+                             -> Result<(Turn, TurnEnd), ParseError> {
+        let num_cols = header.num_cols;
         let mut tiles = Vec::new();
         let mut lines = Vec::new();
-        let mut end = false;
-        let mut turn = 0;
-        while !end {
-            tiles.push(Vec::new());
-            lines.push(Vec::new());
-            let turn_fields = &mut tiles[turn];
-            let turn_lines = &mut lines[turn];
-            for r in 0..num_rows {
-                line_buffer.clear();
-                reader.read_line(&mut line_buffer).unwrap();
-                if line_buffer.is_empty() {
-                    break;
-                }
-                let mut words = line_buffer.split_whitespace();
-                for c in 0..num_cols {
-                    for symbol in words.next().unwrap().chars() {
-                        if symbol == '.' {
+        let mut messages = Vec::new();
+        let mut labels = Vec::new();
+        let mut heat_maps = Vec::new();
+        let mut entity_ids = HashSet::new();
+        for r in 0..header.num_rows {
+            if !reader.read_line()? {
+                break;
+            }
+            let mut words = reader.words();
+            for c in 0..num_cols {
+                let word = words.try_next()
+                    .ok_or_else(|| words.missing(&format!("cell {} in board row", c)))?;
+                let position_index = (r * num_cols + c) as usize;
+                if header.long_symbols {
+                    if word == "." {
+                        continue;
+                    }
+                    for symbol in word.split(',') {
+                        tiles.push(header.tile_index(&words, r, c, symbol, position_index)?);
+                    }
+                } else {
+                    for (i, character) in word.char_indices() {
+                        if character == '.' {
                             continue;
                         }
-                        match palette_map.get(&symbol) {
-                            Some(&pi) => turn_fields.push(Index {
-                                position_index: (r * num_cols + c) as usize,
-                                palette_index: pi,
-                            }),
-                            None => panic!("Unknown symbol: {}", symbol),
-                        };
+                        let symbol = &word[i..i + character.len_utf8()];
+                        tiles.push(header.tile_index(&words, r, c, symbol, position_index)?);
                     }
                 }
             }
-            loop {
-                line_buffer.clear();
-                reader.read_line(&mut line_buffer).unwrap();
-                if line_buffer.is_empty() {
-                    end = true;
+        }
+        let end;
+        loop {
+            if !reader.read_line()? {
+                end = TurnEnd::EndOfFile;
+                break;
+            }
+            let mut words = reader.words();
+            let keyword = match words.try_next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+            match keyword {
+                "end" => {
+                    end = TurnEnd::End;
                     break;
-                }
-                let mut words = line_buffer.split_whitespace();
-                let keyword = words.next().unwrap();
-                match keyword {
-                    "end" => {
-                        end = true;
-                        break;
-                    },
-                    "turn" => break,
-                    "line" => {
-                        let r1 = words.next().unwrap().parse::<u32>().unwrap();
-                        let c1 = words.next().unwrap().parse::<u32>().unwrap();
-                        let r2 = words.next().unwrap().parse::<u32>().unwrap();
-                        let c2 = words.next().unwrap().parse::<u32>().unwrap();
-                        let red   = words.next().unwrap().parse::<f32>().unwrap();
-                        let green = words.next().unwrap().parse::<f32>().unwrap();
-                        let blue  = words.next().unwrap().parse::<f32>().unwrap();
-                        let alpha = words.next().unwrap().parse::<f32>().unwrap();
-                        let layer = words.next().unwrap().parse::<u8>().unwrap();
-                        assert!(layer < 10);
-                        turn_lines.push(Line {
-                            r1,
-                            c1,
-                            r2,
-                            c2,
-                            color: Vector4::new(red, green, blue, alpha),
-                            layer
-                        });
-                    },
-                    _ => (),
-                }
+                },
+                "turn" => {
+                    end = TurnEnd::NextTurn;
+                    break;
+                },
+                "line" => {
+                    let r1 = words.parse::<u32>("r1", "line")?;
+                    let c1 = words.parse::<u32>("c1", "line")?;
+                    let r2 = words.parse::<u32>("r2", "line")?;
+                    let c2 = words.parse::<u32>("c2", "line")?;
+                    let red   = words.parse::<f32>("red", "line")?;
+                    let green = words.parse::<f32>("green", "line")?;
+                    let blue  = words.parse::<f32>("blue", "line")?;
+                    let alpha = words.parse::<f32>("alpha", "line")?;
+                    let layer = words.parse::<u32>("layer", "line")?;
+                    lines.push(Line {
+                        r1,
+                        c1,
+                        r2,
+                        c2,
+                        color: Vector4::new(red, green, blue, alpha),
+                        layer
+                    });
+                },
+                "ent" => {
+                    let id = words.parse::<u32>("id", "entity")?;
+                    if !entity_ids.insert(id) {
+                        return Err(words.error(&id.to_string(), "id not used before in this turn"));
+                    }
+                    let row = words.parse::<u32>("row", "entity")?;
+                    if row >= header.num_rows {
+                        let expected = format!("row below {} in entity", header.num_rows);
+                        return Err(words.error(&row.to_string(), &expected));
+                    }
+                    let col = words.parse::<u32>("column", "entity")?;
+                    if col >= num_cols {
+                        let expected = format!("column below {} in entity", num_cols);
+                        return Err(words.error(&col.to_string(), &expected));
+                    }
+                    let symbol = words.next_word("symbol", "entity")?;
+                    let position_index = (row * num_cols + col) as usize;
+                    let mut index = header.tile_index(&words, row, col, symbol, position_index)?;
+                    index.entity = Some(id);
+                    tiles.push(index);
+                },
+                "msg" | "log" => {
+                    let mut cell = None;
+                    let mut layer = None;
+                    if !words.at_quote() {
+                        let row = words.parse::<u32>("row or text", keyword)?;
+                        let col = words.parse::<u32>("column", keyword)?;
+                        cell = Some((row, col));
+                        layer = Some(words.parse::<u32>("layer", keyword)?);
+                    }
+                    let text = words.quoted("text", keyword)?;
+                    messages.push(Message {
+                        text,
+                        cell,
+                        layer,
+                    });
+                },
+                "label" => {
+                    let row = words.parse::<u32>("row", "label")?;
+                    if row >= header.num_rows {
+                        let expected = format!("row below {} in label", header.num_rows);
+                        return Err(words.error(&row.to_string(), &expected));
+                    }
+                    let col = words.parse::<u32>("column", "label")?;
+                    if col >= num_cols {
+                        let expected = format!("column below {} in label", num_cols);
+                        return Err(words.error(&col.to_string(), &expected));
+                    }
+                    let layer = words.parse::<u32>("layer", "label")?;
+                    let text = if words.at_quote() {
+                        words.quoted("text", "label")?
+                    } else {
+                        words.next_word("text or number", "label")?.to_string()
+                    };
+                    labels.push(Label {
+                        row,
+                        col,
+                        layer,
+                        text,
+                    });
+                },
+                "heat" => {
+                    let layer = words.parse::<u32>("layer", "heat map")?;
+                    heat_maps.push(Arc::new(Self::parse_heat_map(header, layer, reader)?));
+                },
+                _ => (),
             }
-            turn += 1;
         }
-        GameData {
-            tiles,
-            lines,
-            palette,
-            num_rows,
-            num_cols,
-            background_color,
+        Ok((Turn { tiles, lines, messages, labels, heat_maps }, end))
+    }
+
+    /// Parses the rows of values following a `heat` line, `.` marking cells without a value.
+    fn parse_heat_map<R: BufRead>(header: &Header, layer: u32, reader: &mut LineReader<R>)
+                                  -> Result<HeatMap, ParseError> {
+        let num_cells = (header.num_rows * header.num_cols) as usize;
+        let mut values = Vec::with_capacity(num_cells);
+        for _ in 0..header.num_rows {
+            reader.expect_line("heat map row")?;
+            let mut words = reader.words();
+            for c in 0..header.num_cols {
+                let word = words.try_next()
+                    .ok_or_else(|| words.missing(&format!("value of cell {} in heat map row", c)))?;
+                let value = if word == "." {
+                    f32::NAN
+                } else {
+                    word.parse::<f32>().map_err(|_| {
+                        words.error(word, &format!("value of cell {} as f32 in heat map row", c))
+                    })?
+                };
+                values.push(value);
+            }
         }
+        Ok(HeatMap {
+            layer,
+            num_cols: header.num_cols,
+            values,
+        })
     }
+}
 
-    pub fn num_turns(&self) -> u32 {
-        self.tiles.len() as u32
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Cursor;
+    use std::process;
+
+    use parser::ParseError;
+    use cgmath::Vector4;
+
+    use colormap::Colormap;
+    use tween::tween_tiles;
+    use writer::tests::written;
+    use super::GameData;
+    use super::Shape;
+    use super::Waypoint;
+    use super::TURN_CACHE_SIZE;
+
+    const HEADER: &str = "2 3\n0 0 0\na circle 1 0 0 1 1\nb square 0 0 1 1 2\nturn\n";
+
+    fn load(text: &str) -> Result<GameData, ParseError> {
+        let reader = Cursor::new(text.as_bytes().to_vec());
+        let mut game_data = GameData::from_reader(reader, "test.txt")?;
+        game_data.wait()?;
+        Ok(game_data)
     }
 
-    pub fn num_rows(&self) -> u32 {
-        self.num_rows
+    fn load_error(text: &str) -> ParseError {
+        match load(text) {
+            Ok(_) => panic!("loaded invalid file:\n{}", text),
+            Err(error) => error,
+        }
     }
 
-    pub fn num_cols(&self) -> u32 {
-        self.num_cols
+    #[test]
+    fn error_in_grid() {
+        let error = load_error(&format!("{}a . .\n. ab c\nend\n", HEADER));
+        assert_eq!(error.file_name(), "test.txt");
+        assert_eq!(error.line_number(), 7);
+        assert_eq!(error.word_index(), Some(3));
+        assert_eq!(error.found(), Some("c"));
+        assert_eq!(error.expected(), Some("palette symbol in cell (1, 2)"));
+        assert_eq!(
+            error.to_string(),
+            "test.txt:7, word 3: expected palette symbol in cell (1, 2), found \"c\"",
+        );
     }
 
-    pub fn background_color(&self) -> Vector3<f32> {
-        self.background_color
+    #[test]
+    fn missing_cell_in_grid() {
+        let error = load_error(&format!("{}a . .\n. b\nend\n", HEADER));
+        assert_eq!(error.line_number(), 7);
+        assert_eq!(error.word_index(), Some(3));
+        assert_eq!(error.found(), None);
+        assert_eq!(
+            error.to_string(),
+            "test.txt:7, word 3: expected cell 2 in board row, found end of line",
+        );
     }
 
-    pub fn tiles(&self, turn: u32) -> TileIterator {
-        TileIterator {
-            inner: self.tiles[turn as usize].iter(),
-            palette: &self.palette,
-            num_cols: self.num_cols,
+    #[test]
+    fn error_in_palette_entry() {
+        let error = load_error("2 3\n0 0 0\na circle 1 zero 0 1 1\nturn\n. . .\n. . .\n");
+        assert_eq!(error.line_number(), 3);
+        assert_eq!(error.word_index(), Some(4));
+        assert_eq!(error.found(), Some("zero"));
+        assert_eq!(
+            error.to_string(),
+            "test.txt:3, word 4: expected green as f32 in palette entry, found \"zero\"",
+        );
+    }
+
+    #[test]
+    fn end_of_file_in_header() {
+        let error = load_error("2 3\n0 0 0\na circle 1 0 0 1 1\n");
+        assert_eq!(error.line_number(), 4);
+        assert_eq!(error.word_index(), None);
+        assert_eq!(error.found(), None);
+        assert_eq!(
+            error.to_string(),
+            "test.txt:4: expected palette entry, layer or `turn`, found end of file",
+        );
+    }
+
+    #[test]
+    fn turns_without_end() {
+        let game_data = load(&format!("{}a . .\n. . b\nturn\n. . .\n. . .\n", HEADER)).unwrap();
+        assert_eq!(game_data.num_turns(), 2);
+        assert!(game_data.is_complete());
+    }
+
+    #[test]
+    fn file_changed_after_indexing() {
+        let file_name = env::temp_dir().join(format!("insight-changed-{}.txt", process::id()));
+        let file_name = file_name.to_str().unwrap();
+        let mut text = HEADER.to_string();
+        for turn in 0..TURN_CACHE_SIZE + 1 {
+            if turn > 0 {
+                text.push_str("turn\n");
+            }
+            text.push_str("a . .\n. . b\n");
+        }
+        fs::write(file_name, &text).unwrap();
+        let mut game_data = GameData::load(file_name, false).unwrap();
+        game_data.wait().unwrap();
+        // the first turn has been evicted from the cache, so it is read from the file again
+        fs::write(file_name, text.replace("a . .", "a . x")).unwrap();
+        assert_eq!(game_data.tiles(0).count(), 0);
+        let error = match game_data.update() {
+            Ok(_) => panic!("the changed turn was not reported"),
+            Err(error) => error,
+        };
+        assert_eq!(error.line_number(), 6);
+        assert_eq!(error.found(), Some("x"));
+        assert!(game_data.update().is_ok());
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn heat_maps() {
+        let text = "2 3\n0 0 0\nheat 4 redblue -1 1\nheat 5 grayscale\nturn\n. . .\n. . .\n\
+                    heat 4\n0.5 . 1\n-1 0 2\nend\n";
+        let game_data = load(text).unwrap();
+        let scale = game_data.heat_scale(4);
+        assert_eq!((scale.colormap, scale.range), (Colormap::RedBlue, Some((-1.0, 1.0))));
+        let scale = game_data.heat_scale(5);
+        assert_eq!((scale.colormap, scale.range), (Colormap::Grayscale, None));
+        assert_eq!(game_data.heat_scale(6).colormap, Colormap::Viridis);
+
+        let heat_maps: Vec<_> = game_data.heat_maps(0).collect();
+        assert_eq!(heat_maps.len(), 1);
+        assert_eq!(heat_maps[0].layer, 4);
+        assert_eq!(heat_maps[0].value(0, 0), Some(0.5));
+        assert_eq!(heat_maps[0].value(0, 1), None);
+        assert_eq!(heat_maps[0].value(1, 2), Some(2.0));
+        assert_eq!(heat_maps[0].range(), Some((-1.0, 2.0)));
+    }
+
+    #[test]
+    fn unknown_colormap() {
+        let error = load_error("2 3\n0 0 0\nheat 4 rainbow\nturn\n. . .\n. . .\n");
+        assert_eq!((error.line_number(), error.word_index()), (3, Some(3)));
+        assert_eq!(error.found(), Some("rainbow"));
+        assert_eq!(error.expected(), Some("colormap in heat scale (viridis, redblue, grayscale)"));
+    }
+
+    #[test]
+    fn heat_scale_range_not_increasing() {
+        let error = load_error("2 3\n0 0 0\nheat 4 viridis 2 1\nturn\n. . .\n. . .\n");
+        assert_eq!((error.line_number(), error.word_index()), (3, Some(5)));
+        assert_eq!(error.found(), Some("1"));
+        assert_eq!(error.expected(), Some("maximum above 2 in heat scale"));
+        assert!(load_error("2 3\n0 0 0\nheat 4 viridis 1 1\nturn\n. . .\n. . .\n")
+            .to_string()
+            .ends_with("expected maximum above 1 in heat scale, found \"1\""));
+    }
+
+    #[test]
+    fn heat_map_with_missing_row() {
+        let error = load_error(&format!("{}. . .\n. . .\nheat 4\n1 2 3\nend\n", HEADER));
+        assert_eq!((error.line_number(), error.word_index()), (10, Some(1)));
+        assert_eq!(error.found(), Some("end"));
+        assert_eq!(error.expected(), Some("value of cell 0 as f32 in heat map row"));
+    }
+
+    #[test]
+    fn heat_map_with_missing_value() {
+        let error = load_error(&format!("{}. . .\n. . .\nheat 4\n1 2\n4 5 6\n", HEADER));
+        assert_eq!((error.line_number(), error.word_index()), (9, Some(3)));
+        assert_eq!(error.found(), None);
+        assert_eq!(error.expected(), Some("value of cell 2 in heat map row"));
+    }
+
+    /// The symbol, cell, shape and layer of every tile of a turn.
+    fn tile_summary(game_data: &GameData, turn: u32) -> Vec<(String, u32, u32, Shape, u32)> {
+        game_data.tiles_with_symbols(turn).into_iter()
+            .map(|(symbol, tile)| (symbol.to_string(), tile.row, tile.col, tile.shape, tile.layer))
+            .collect()
+    }
+
+    #[test]
+    fn single_and_long_symbols() {
+        let single = load("2 3\n0 0 0\na circle 1 0 0 1 1\nb square 0 1 0 1 2\n\
+                           hill circle 0 0 1 1 3\nturn\nab . h\n. b .\nend\n").unwrap();
+        let long = load("2 3\n0 0 0\nsymbols long\nant circle 1 0 0 1 1\n\
+                         base square 0 1 0 1 2\nhill circle 0 0 1 1 3\nturn\n\
+                         ant,base . hill\n. base .\nend\n").unwrap();
+        let single_tiles = tile_summary(&single, 0);
+        assert_eq!(single_tiles, vec![
+            ("a".to_string(), 0, 0, Shape::Circle, 1),
+            ("b".to_string(), 0, 0, Shape::Square, 2),
+            ("h".to_string(), 0, 2, Shape::Circle, 3),
+            ("b".to_string(), 1, 1, Shape::Square, 2),
+        ]);
+        let long_tiles = tile_summary(&long, 0);
+        let long_symbols: Vec<_> = long_tiles.iter().map(|tile| tile.0.as_str()).collect();
+        assert_eq!(long_symbols, vec!["ant", "base", "hill", "base"]);
+        let positions = |tiles: &[(String, u32, u32, Shape, u32)]| {
+            tiles.iter().map(|tile| (tile.1, tile.2, tile.3, tile.4)).collect::<Vec<_>>()
+        };
+        assert_eq!(positions(&single_tiles), positions(&long_tiles));
+    }
+
+    #[test]
+    fn shape_names() {
+        let mut text = "1 14\n0 0 0\n".to_string();
+        let shapes = [
+            "square", "s", "circle", "c", "triangle", "d", "ring", "x", "arrow_north", ">",
+            "arrow_south", "<", "cross", "diamond",
+        ];
+        let symbols = "abcdefghijklmn";
+        for (symbol, shape) in symbols.chars().zip(shapes.iter()) {
+            text.push_str(&format!("{} {} 1 1 1 1 1\n", symbol, shape));
         }
+        let row: Vec<String> = symbols.chars().map(|symbol| symbol.to_string()).collect();
+        text.push_str(&format!("turn\n{}\nend\n", row.join(" ")));
+        let game_data = load(&text).unwrap();
+        let shapes: Vec<_> = game_data.tiles(0).map(|tile| tile.shape).collect();
+        assert_eq!(shapes, vec![
+            Shape::Square,
+            Shape::Square,
+            Shape::Circle,
+            Shape::Circle,
+            Shape::Triangle,
+            Shape::Diamond,
+            Shape::Ring,
+            Shape::Cross,
+            Shape::ArrowNorth,
+            Shape::ArrowEast,
+            Shape::ArrowSouth,
+            Shape::ArrowWest,
+            Shape::Cross,
+            Shape::Diamond,
+        ]);
+    }
+
+    #[test]
+    fn unknown_shape() {
+        let error = load_error("1 1\n0 0 0\na star 1 0 0 1 1\nturn\na\n");
+        assert_eq!((error.line_number(), error.word_index()), (3, Some(2)));
+        assert_eq!(error.found(), Some("star"));
+        assert!(error.expected().unwrap().starts_with("shape in palette entry (square or s, "));
     }
 
-    pub fn lines(&self, turn: u32) -> slice::Iter<Line> {
-        self.lines[turn as usize].iter()
+    #[test]
+    fn trajectories() {
+        let red = Vector4::new(1.0, 0.0, 0.0, 1.0);
+        let game_data = written(3, 4, |writer| {
+            writer.add_palette_entry("a", Shape::Circle, red, 1);
+            writer.set_wrap(true);
+            // entity 7 walks east over the edge of the board and is gone for a turn
+            for &(turn, col) in &[(0, 3), (1, 0), (2, 1), (3, 1)] {
+                if turn != 2 {
+                    writer.entity(7, 1, col, "a");
+                }
+                writer.entity(2, turn % 3, 0, "a");
+                writer.end_turn().unwrap();
+            }
+        });
+        assert_eq!(game_data.entity_ids(), vec![2, 7]);
+        assert_eq!(game_data.trajectory(7), &[
+            Waypoint { turn: 0, row: 1, col: 3 },
+            Waypoint { turn: 1, row: 1, col: 0 },
+            Waypoint { turn: 3, row: 1, col: 1 },
+        ]);
+        assert_eq!(game_data.trajectory(2).len(), 4);
+        assert!(game_data.trajectory(5).is_empty());
+        assert_eq!(game_data.entity_position(7, 1), Some(Waypoint { turn: 1, row: 1, col: 0 }));
+        assert_eq!(game_data.entity_position(7, 2), None);
+        assert_eq!(game_data.entity_position(7, 4), None);
+
+        // halfway to the next turn the entity sticks out of both sides of the board
+        let mut positions: Vec<_> = tween_tiles(&game_data, 0, 0.5).into_iter()
+            .filter(|tile| tile.row == 1.0)
+            .map(|tile| tile.col)
+            .collect();
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(positions, vec![-0.5, 3.5]);
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,6 @@
+// implement_vertex! of glium 0.21 expands to the deprecated mem::uninitialized
+#![allow(deprecated)]
+
 use std::fs::File;
 use std::io::BufReader;
 use std::io::Read;
@@ -12,18 +15,31 @@ use glium::program::Program;
 use glium::DrawParameters;
 use glium::Blend;
 use glium::Frame;
+use glium::Rect;
+use glium::Vertex;
+use glium::texture::Texture2d;
+use glium::texture::RawImage2d;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::uniforms::MinifySamplerFilter;
 
 use cgmath::Matrix4;
-use cgmath::SquareMatrix;
+use cgmath::Vector3;
 
 use game_data::GameData;
 use game_data::Shape;
+use torus;
+use tween;
+use layers::LayerVisibility;
+use overlay::Overlay;
+use overlay::OverlayVertex;
+use view::View;
+use font;
 
 #[derive(Copy, Clone)]
 struct MyTile {
     position: [f32; 3],
     color: [f32; 4],
-    radius2: f32,
+    shape: i32,
 }
 
 #[derive(Copy, Clone)]
@@ -38,22 +54,27 @@ struct MyVertex {
     position: [f32; 3],
 }
 
-implement_vertex!(MyTile, position, color, radius2);
+implement_vertex!(MyTile, position, color, shape);
 implement_vertex!(MyLine, start, end, color);
 implement_vertex!(MyVertex, position);
+implement_vertex!(OverlayVertex, position, tex_coords, color);
 
 pub struct Graphics {
     tile_vertex_data: Vec<MyTile>,
     line_vertex_data: Vec<MyLine>,
+    label_overlay: Overlay,
     background_vertex_buffer: VertexBuffer<MyVertex>,
     background_index_buffer: IndexBuffer<u32>,
     tiles_program: Program,
     lines_program: Program,
     background_program: Program,
-    width: u32,
-    height: u32,
-    transformation_matrix: Matrix4<f32>,
-    layer_switches: [bool; 10],
+    overlay_program: Program,
+    font_atlas: Texture2d,
+    view: View,
+    /// The transformation of every copy of the board, with the part of the window it may draw
+    /// into.
+    board_copies: Vec<(Matrix4<f32>, Option<Rect>)>,
+    layers: LayerVisibility,
 }
 
 impl Graphics {
@@ -66,6 +87,8 @@ impl Graphics {
         let lines_vss = Self::load_shader_source("shader_src/lines.vert");
         let lines_gss = Self::load_shader_source("shader_src/lines.geom");
         let lines_fss = Self::load_shader_source("shader_src/lines.frag");
+        let overlay_vss = Self::load_shader_source("shader_src/overlay.vert");
+        let overlay_fss = Self::load_shader_source("shader_src/overlay.frag");
 
         let tiles_program = Program::from_source(
             display,
@@ -88,6 +111,19 @@ impl Graphics {
             None,
         ).unwrap();
 
+        let overlay_program = Program::from_source(
+            display,
+            &overlay_vss,
+            &overlay_fss,
+            None,
+        ).unwrap();
+
+        let font_image = RawImage2d::from_raw_rgba(
+            font::atlas(),
+            (font::ATLAS_WIDTH, font::ATLAS_HEIGHT),
+        );
+        let font_atlas = Texture2d::new(display, font_image).unwrap();
+
         let height = num_rows as f32;
         let width = num_cols as f32;
         let background_vertex_data = [
@@ -108,74 +144,189 @@ impl Graphics {
         Graphics {
             tile_vertex_data: Vec::new(),
             line_vertex_data: Vec::new(),
+            label_overlay: Overlay::new(),
             background_vertex_buffer,
             background_index_buffer,
-            tiles_program: tiles_program,
-            lines_program: lines_program,
+            tiles_program,
+            lines_program,
             background_program,
-            width: 0,
-            height: 0,
-            transformation_matrix: Matrix4::identity(),
-            layer_switches: [true; 10],
+            overlay_program,
+            font_atlas,
+            view: View::new(0, 0),
+            board_copies: Vec::new(),
+            layers: LayerVisibility::new(),
         }
     }
 
-    pub fn draw_turn(&mut self, game_data: &GameData, turn: u32, display: &Display) {
-        let board_width = game_data.num_cols() as f32;
-        let board_height = game_data.num_rows() as f32;
-        let board_ratio = if game_data.num_rows() != 0 {
-            board_width / board_height
-        } else {
-            0.0
-        };
-        let screen_ratio = if self.height != 0 {
-            self.width as f32 / self.height as f32
-        } else {
-            0.0
-        };
-        let x_offset;
-        let y_offset;
-        let x_scaling;
-        let y_scaling;
-        if screen_ratio > board_ratio {
-            x_scaling = 2.0 / board_height / screen_ratio;
-            y_scaling = 2.0 / board_height;
-            x_offset = -board_ratio / screen_ratio;
-            y_offset = -1.0;
-        } else {
-            x_scaling = 2.0 / board_width;
-            y_scaling = 2.0 / board_width * screen_ratio;
-            x_offset = -1.0;
-            y_offset = -screen_ratio / board_ratio;
-        }
-        self.transformation_matrix = Matrix4::new(
-            x_scaling,  0.0,       0.0, 0.0,
-            0.0,        y_scaling, 0.0, 0.0,
-            0.0,        0.0,       1.0, 0.0,
-            x_offset,   y_offset,  0.0, 1.0f32,
-        );
+    /// Draws a turn with the overlay on top of it.
+    ///
+    /// With a progress above 0 the tiles are drawn on their way to the next turn.
+    pub fn draw_turn(&mut self, game_data: &GameData, turn: u32, progress: f32,
+                     overlay: &Overlay, display: &Display) {
+        self.view.update(game_data.num_rows(), game_data.num_cols());
+        self.update_board_copies(game_data.num_rows(), game_data.num_cols(), game_data.wraps());
 
         let mut frame = display.draw();
         frame.clear_color(0.0, 0.0, 0.0, 1.0);
 
         self.draw_background(game_data, &mut frame);
-        self.draw_tiles(game_data, turn, &mut frame, &display);
-        self.draw_lines(game_data, turn, &mut frame, &display);
+        if turn < game_data.num_turns() {
+            self.draw_heat_maps(game_data, turn, &mut frame, display);
+            self.draw_tiles(game_data, turn, progress, &mut frame, display);
+            self.draw_lines(game_data, turn, &mut frame, display);
+            self.draw_labels(game_data, turn, &mut frame, display);
+        }
+        self.draw_overlay(overlay, &mut frame, display);
 
         frame.finish().unwrap();
     }
 
     pub fn toggle_layer(&mut self, layer: u32) {
-        self.layer_switches[layer as usize] ^= true;
+        self.layers.toggle(layer);
+    }
+
+    pub fn is_layer_visible(&self, layer: u32) -> bool {
+        self.layers.is_visible(layer)
+    }
+
+    /// The layers that are toggled off, in ascending order.
+    pub fn hidden_layers(&self) -> Vec<u32> {
+        self.layers.hidden()
     }
 
     pub fn set_view_port(&mut self, width: u32, height: u32) {
-        self.width = width;
-        self.height = height;
+        self.view.set_size(width, height);
+    }
+
+    /// Maps a position in the window, in pixels from the top left corner, to the cell under it.
+    pub fn screen_to_cell(&self, x: f32, y: f32, num_rows: u32, num_cols: u32)
+                          -> Option<(u32, u32)> {
+        self.view.screen_to_cell(x, y, num_rows, num_cols)
+    }
+
+    /// Scrolls the view over the board by the given number of cells to the right and down,
+    /// wrapping around at the edges.
+    pub fn scroll(&mut self, right: f32, down: f32, num_rows: u32, num_cols: u32) {
+        self.view.scroll(right, down, num_rows, num_cols);
+    }
+
+    /// Zooms in by a factor, or out for factors below 1, keeping the given window position in
+    /// place.
+    pub fn zoom(&mut self, factor: f32, x: f32, y: f32) {
+        self.view.zoom(factor, x, y);
+    }
+
+    /// Moves the board by a distance in window pixels.
+    pub fn pan(&mut self, right: f32, down: f32) {
+        self.view.pan(right, down);
+    }
+
+    /// Recomputes where the board is in the window, so that the overlay can be placed on the
+    /// board before the turn is drawn.
+    pub fn update_view(&mut self, num_rows: u32, num_cols: u32) {
+        self.view.update(num_rows, num_cols);
+    }
+
+    /// Moves the view to keep a cell in the center of the window, scrolling wrapping boards.
+    pub fn follow(&mut self, row: u32, col: u32, num_rows: u32, num_cols: u32, wrap: bool) {
+        self.view.follow(row, col, num_rows, num_cols, wrap);
+    }
+
+    /// Maps the center of a cell to window pixels.
+    pub fn cell_center(&self, row: u32, col: u32, num_rows: u32, num_cols: u32) -> (f32, f32) {
+        self.view.cell_center(row, col, num_rows, num_cols)
+    }
+
+    /// The size of a cell in window pixels.
+    pub fn cell_size(&self) -> f32 {
+        self.view.cell_size()
+    }
+
+    /// Fits the whole unscrolled board into the window again.
+    pub fn reset_view(&mut self) {
+        self.view.reset();
+    }
+
+    /// Scrolls like `scroll`, but by a distance in window pixels.
+    pub fn scroll_pixels(&mut self, right: f32, down: f32, num_rows: u32, num_cols: u32) {
+        self.view.scroll_pixels(right, down, num_rows, num_cols);
+    }
+
+    /// The center of the window in pixels.
+    pub fn center(&self) -> (f32, f32) {
+        self.view.center()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.view.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.view.height()
+    }
+
+    /// Places the copies of the board needed to show it scrolled.
+    ///
+    /// When scrolled or wrapping, each copy is clipped to its own part of the board, since tiles
+    /// sliding over the edges stick out of it and are drawn on the other side instead.
+    fn update_board_copies(&mut self, num_rows: u32, num_cols: u32, wrap: bool) {
+        let transformation_matrix = self.view.transformation_matrix();
+        let copies = self.view.board_copies(num_rows, num_cols);
+        let clip = wrap || copies.len() > 1;
+        let board_width = num_cols as f32;
+        let board_height = num_rows as f32;
+        self.board_copies = copies.iter()
+            .map(|copy| {
+                let transformation =
+                    transformation_matrix * Matrix4::from_translation(copy.extend(0.0));
+                if !clip {
+                    return (transformation, None);
+                }
+                let (left, top, right, bottom) = self.view.board_area(
+                    copy.x.max(0.0),
+                    copy.y.max(0.0),
+                    (copy.x + board_width).min(board_width),
+                    (copy.y + board_height).min(board_height),
+                );
+                let (left, top, right, bottom) =
+                    (left.round(), top.round(), right.round(), bottom.round());
+                let scissor = Rect {
+                    left: left as u32,
+                    bottom: (self.view.height() as f32 - bottom) as u32,
+                    width: (right - left).max(0.0) as u32,
+                    height: (bottom - top).max(0.0) as u32,
+                };
+                (transformation, Some(scissor))
+            })
+            .collect();
+    }
+
+    /// Draws points once for every copy of the board.
+    fn draw_board<V: Vertex>(&self, vertex_buffer: &VertexBuffer<V>, program: &Program,
+                             blend: Blend, frame: &mut Frame) {
+        for &(transformation, scissor) in &self.board_copies {
+            let draw_parameters = DrawParameters {
+                blend,
+                scissor,
+                ..Default::default()
+            };
+            let transformation_matrix_uniform: [[f32; 4]; 4] = transformation.into();
+            let uniforms = uniform! {
+                trafo_matrix: transformation_matrix_uniform,
+            };
+            frame.draw(
+                vertex_buffer,
+                NoIndices(PrimitiveType::Points),
+                program,
+                &uniforms,
+                &draw_parameters,
+            ).unwrap();
+        }
     }
 
     fn draw_background(&mut self, game_data: &GameData, frame: &mut Frame) {
-        let transformation_matrix_uniform: [[f32; 4]; 4] = self.transformation_matrix.into();
+        let transformation_matrix_uniform: [[f32; 4]; 4] =
+            self.view.transformation_matrix().into();
         let background_color_uniform: [f32; 3] = game_data.background_color().into();
         let background_uniforms = uniform! {
             trafo_matrix: transformation_matrix_uniform,
@@ -192,47 +343,78 @@ impl Graphics {
         ).unwrap();
     }
 
-    fn draw_tiles(&mut self, game_data: &GameData, turn: u32, frame: &mut Frame,
-                  display: &Display) {
+    fn draw_heat_maps(&mut self, game_data: &GameData, turn: u32, frame: &mut Frame,
+                      display: &Display) {
         self.tile_vertex_data.clear();
 
         let num_rows = game_data.num_rows();
-        for tile in game_data.tiles(turn) {
-            if !self.layer_switches[tile.layer as usize] {
+        let num_cols = game_data.num_cols();
+        for heat_map in game_data.heat_maps(turn) {
+            if !self.is_layer_visible(heat_map.layer) {
                 continue;
             }
-            let x = tile.col as f32;
-            let y = (num_rows - tile.row - 1) as f32;
-            let z = 1.0 - tile.layer as f32 / 100.0;
-            let radius2 = match tile.shape {
-                Shape::Square => 1.0,
-                Shape::Circle => 0.25,
-            };
-            self.tile_vertex_data.push(MyTile {
-                position: [x, y, z],
-                color: tile.color.into(),
-                radius2,
-            });
+            let heat_scale = game_data.heat_scale(heat_map.layer);
+            let bounds = heat_scale.bounds(&heat_map);
+            let z = layer_depth(heat_map.layer);
+            for (index, &value) in heat_map.values().iter().enumerate() {
+                if value.is_nan() {
+                    continue;
+                }
+                let row = index as u32 / num_cols;
+                let col = index as u32 % num_cols;
+                let color: Vector3<f32> = heat_scale.color(value, bounds);
+                self.tile_vertex_data.push(MyTile {
+                    position: [col as f32, (num_rows - row - 1) as f32, z],
+                    color: color.extend(1.0).into(),
+                    shape: Shape::Square as i32,
+                });
+            }
         }
         let vertex_buffer = VertexBuffer::new(display, &self.tile_vertex_data).unwrap();
 
-        let transformation_matrix_uniform: [[f32; 4]; 4] = self.transformation_matrix.into();
-        let uniforms = uniform! {
-            trafo_matrix: transformation_matrix_uniform,
-        };
+        // draw heat maps
+        self.draw_board(&vertex_buffer, &self.tiles_program, Default::default(), frame);
+    }
+
+    fn draw_tiles(&mut self, game_data: &GameData, turn: u32, progress: f32, frame: &mut Frame,
+                  display: &Display) {
+        self.tile_vertex_data.clear();
+
+        let num_rows = game_data.num_rows();
+        if progress > 0.0 {
+            for tile in tween::tween_tiles(game_data, turn, progress) {
+                if !self.is_layer_visible(tile.layer) {
+                    continue;
+                }
+                self.tile_vertex_data.push(MyTile {
+                    position: [
+                        tile.col,
+                        num_rows as f32 - tile.row - 1.0,
+                        layer_depth(tile.layer),
+                    ],
+                    color: tile.color.into(),
+                    shape: tile.shape as i32,
+                });
+            }
+        } else {
+            for tile in game_data.tiles(turn) {
+                if !self.is_layer_visible(tile.layer) {
+                    continue;
+                }
+                let x = tile.col as f32;
+                let y = (num_rows - tile.row - 1) as f32;
+                let z = layer_depth(tile.layer);
+                self.tile_vertex_data.push(MyTile {
+                    position: [x, y, z],
+                    color: tile.color.into(),
+                    shape: tile.shape as i32,
+                });
+            }
+        }
+        let vertex_buffer = VertexBuffer::new(display, &self.tile_vertex_data).unwrap();
 
         // draw tiles
-        let draw_parameters = DrawParameters {
-            blend: Blend::alpha_blending(),
-            ..Default::default()
-        };
-        frame.draw(
-            &vertex_buffer,
-            &NoIndices(PrimitiveType::Points),
-            &self.tiles_program,
-            &uniforms,
-            &draw_parameters,
-        ).unwrap();
+        self.draw_board(&vertex_buffer, &self.tiles_program, Blend::alpha_blending(), frame);
     }
 
     fn draw_lines(&mut self, game_data: &GameData, turn: u32, frame: &mut Frame,
@@ -241,36 +423,61 @@ impl Graphics {
 
         let num_rows = game_data.num_rows();
         for line in game_data.lines(turn) {
-            if !self.layer_switches[line.layer as usize] {
+            if !self.is_layer_visible(line.layer) {
                 continue;
             }
-            let x1 = line.c1 as f32;
-            let y1 = (num_rows - line.r1 - 1) as f32;
-            let x2 = line.c2 as f32;
-            let y2 = (num_rows - line.r2 - 1) as f32;
-            let z = 1.0 - line.layer as f32 / 100.0;
-            self.line_vertex_data.push(MyLine {
-                start: [x1, y1, z],
-                end: [x2, y2, z],
-                color: line.color.into(),
-            });
+            let z = layer_depth(line.layer);
+            let segments = torus::line_segments(
+                line.r1,
+                line.c1,
+                line.r2,
+                line.c2,
+                num_rows,
+                game_data.num_cols(),
+                game_data.wraps(),
+            );
+            for [start, end] in segments {
+                // the line shader starts from the lower left corner of a cell
+                self.line_vertex_data.push(MyLine {
+                    start: [start[0] - 0.5, num_rows as f32 - start[1] - 0.5, z],
+                    end: [end[0] - 0.5, num_rows as f32 - end[1] - 0.5, z],
+                    color: line.color.into(),
+                });
+            }
         }
         let vertex_buffer = VertexBuffer::new(display, &self.line_vertex_data).unwrap();
 
-        let transformation_matrix_uniform: [[f32; 4]; 4] = self.transformation_matrix.into();
+        // draw lines
+        self.draw_board(&vertex_buffer, &self.lines_program, Blend::alpha_blending(), frame);
+    }
+
+    fn draw_labels(&mut self, game_data: &GameData, turn: u32, frame: &mut Frame,
+                   display: &Display) {
+        self.label_overlay.clear();
+        self.label_overlay.labels(game_data, turn, &self.layers, &self.view);
+
+        self.draw_overlay(&self.label_overlay, frame, display);
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay, frame: &mut Frame, display: &Display) {
+        let vertex_buffer = VertexBuffer::new(display, overlay.vertices()).unwrap();
+
         let uniforms = uniform! {
-            trafo_matrix: transformation_matrix_uniform,
+            screen_size: [self.view.width() as f32, self.view.height() as f32],
+            font_atlas: self.font_atlas.sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
         };
 
-        // draw tiles
+        // draw overlay
         let draw_parameters = DrawParameters {
             blend: Blend::alpha_blending(),
             ..Default::default()
         };
         frame.draw(
             &vertex_buffer,
-            &NoIndices(PrimitiveType::Points),
-            &self.lines_program,
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.overlay_program,
             &uniforms,
             &draw_parameters,
         ).unwrap();
@@ -284,4 +491,10 @@ impl Graphics {
             .expect("Error while reading shader source!");
         shader_source
     }
-}
\ No newline at end of file
+}
+
+/// Maps a layer to a depth within the clipping range, decreasing with the layer like the
+/// depths of the first ten layers used to.
+fn layer_depth(layer: u32) -> f32 {
+    1.0 / (1.0 + layer as f32)
+}
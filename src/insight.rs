@@ -1,6 +1,7 @@
 use std::thread;
 use std::time::Duration;
 use std::time::Instant;
+use std::collections::BTreeSet;
 
 use glium::glutin;
 use glium::glutin::WindowBuilder;
@@ -8,10 +9,85 @@ use glium::glutin::ContextBuilder;
 use glium::glutin::EventsLoop;
 use glium::glutin::VirtualKeyCode;
 use glium::glutin::ElementState;
+use glium::glutin::MouseButton;
+use glium::glutin::MouseScrollDelta;
 use glium::backend::glutin::Display;
 
+use cgmath::Vector4;
+
 use graphics::Graphics;
+use overlay;
+use overlay::Overlay;
+use overlay::PANEL_PADDING;
+use overlay::PANEL_COLOR;
+use overlay::TEXT_COLOR;
+use overlay::TITLE_COLOR;
 use game_data::GameData;
+use parser::ParseError;
+
+/// Size of a font pixel of the overlay text in window pixels.
+const TEXT_SCALE: f32 = 2.0;
+/// Maximal width of the log panel and the HUD in characters.
+const LOG_WIDTH: usize = 60;
+/// How much one step of the mouse wheel or the zoom keys zooms.
+const ZOOM_STEP: f32 = 1.25;
+/// How far the pan keys move the board, in window pixels.
+const PAN_STEP: f32 = 64.0;
+/// How far the cursor may move while the left button is down for a click, in window pixels.
+const CLICK_DISTANCE: f32 = 4.0;
+
+/// Number of past turns shown in the trail of the selected entity.
+const TRAIL_LENGTH: u32 = 20;
+const OUTLINE_WIDTH: f32 = 2.0;
+const SELECTION_COLOR: [f32; 4] = [1.0, 0.3, 0.9, 1.0];
+
+/// Size of the gradient in heat map legends, in window pixels.
+const LEGEND_WIDTH: f32 = 200.0;
+const LEGEND_HEIGHT: f32 = 12.0;
+const LEGEND_STEPS: usize = 64;
+
+const TRACK_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+const PROGRESS_COLOR: [f32; 4] = [0.45, 0.45, 0.5, 1.0];
+const ANNOTATION_COLOR: [f32; 4] = [0.3, 0.8, 1.0, 1.0];
+const BOOKMARK_COLOR: [f32; 4] = [1.0, 0.85, 0.4, 1.0];
+const HANDLE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// The bar of the timeline along the bottom of the window, in window pixels.
+#[derive(Clone, Copy)]
+struct Track {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Track {
+    fn new(screen_width: f32, screen_height: f32, num_turns: u32) -> Track {
+        let text_width = overlay::text_width(&turn_counter(num_turns, num_turns), TEXT_SCALE);
+        let x = text_width + 2.0 * PANEL_PADDING;
+        Track {
+            x,
+            y: screen_height - timeline_height() + PANEL_PADDING,
+            width: (screen_width - x - PANEL_PADDING).max(0.0),
+            height: timeline_height() - 2.0 * PANEL_PADDING,
+        }
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// The turn under a horizontal position, clamped to the existing turns.
+    fn turn_at(&self, x: f32, num_turns: u32) -> u32 {
+        let turn = ((x - self.x) / self.width * num_turns as f32).max(0.0) as u32;
+        turn.min(num_turns.saturating_sub(1))
+    }
+
+    /// The left edge of the part of the bar belonging to a turn.
+    fn position(&self, turn: u32, num_turns: u32) -> f32 {
+        self.x + self.width * turn as f32 / num_turns.max(1) as f32
+    }
+}
 
 pub struct Insight {
     game_data: GameData,
@@ -24,10 +100,32 @@ pub struct Insight {
     last_frame: Instant,
     playing: bool,
     play_speed: f64,
+    follow: bool,
+    layer_bank: u32,
+    overlay: Overlay,
+    show_log: bool,
+    cursor: Option<(f32, f32)>,
+    scrolling: bool,
+    panning: bool,
+    scrubbing: bool,
+    show_timeline: bool,
+    bookmarks: BTreeSet<u32>,
+    goto_input: Option<String>,
+    target_turn: Option<u32>,
+    show_hud: bool,
+    smooth: bool,
+    press: Option<(f32, f32)>,
+    selection: Option<u32>,
+    follow_selection: bool,
+    /// The entity and the turn the view was last centered on while following the selection.
+    centered_on: Option<(u32, u32)>,
 }
 
 impl Insight {
-    pub fn open(file_name: &str) -> Insight {
+    /// Opens the viewer window.
+    ///
+    /// When following, playback jumps to new turns as they arrive.
+    pub fn new(board: GameData, follow: bool) -> Insight {
         let events_loop = EventsLoop::new();
         let window = WindowBuilder::new()
             .with_fullscreen(events_loop.get_available_monitors().next())
@@ -36,7 +134,6 @@ impl Insight {
             .with_vsync(true);
         let display = Display::new(window, context, &events_loop).unwrap();
 
-        let board = GameData::load(file_name);
         let graphics = Graphics::new(board.num_rows(), board.num_cols(), &display);
         Insight {
             game_data: board,
@@ -49,27 +146,86 @@ impl Insight {
             last_frame: Instant::now(),
             playing: true,
             play_speed: 1.0,
+            follow,
+            layer_bank: 0,
+            overlay: Overlay::new(),
+            show_log: true,
+            cursor: None,
+            scrolling: false,
+            panning: false,
+            scrubbing: false,
+            show_timeline: true,
+            bookmarks: BTreeSet::new(),
+            goto_input: None,
+            target_turn: None,
+            show_hud: true,
+            smooth: false,
+            press: None,
+            selection: None,
+            follow_selection: false,
+            centered_on: None,
         }
     }
 
-    pub fn run(&mut self) {
+    /// Stops playback and jumps to a turn as soon as it has been indexed, or to the last turn
+    /// if the game turns out to be shorter.
+    pub fn go_to_turn(&mut self, turn: u32) {
+        self.playing = false;
+        self.target_turn = Some(turn);
+    }
+
+    pub fn run(&mut self) -> Result<(), ParseError> {
         while !self.closing {
             self.handle_events();
-            let play_interval = Duration::new(0, ((1.0 / (2.0 * self.play_speed)) * 1e9) as u32);
-            if self.playing && self.last_frame.elapsed() >= play_interval {
-                if self.turn < self.game_data.num_turns() - 1 {
-                    self.turn += 1;
+            // only jump along once the newest turn was being shown, a game read from a pipe
+            // starts at its first turn
+            let num_turns = self.game_data.num_turns();
+            let at_end = num_turns > 0 && self.turn + 1 >= num_turns;
+            if self.game_data.update()? {
+                if at_end && self.follow && self.playing {
+                    self.turn = self.game_data.num_turns() - 1;
+                }
+                // the timeline and the HUD show the number of turns
+                self.need_redraw = true;
+            }
+            if let Some(target_turn) = self.target_turn {
+                let num_turns = self.game_data.num_turns();
+                if target_turn < num_turns || (self.game_data.is_complete() && num_turns > 0) {
+                    self.turn = target_turn.min(num_turns - 1);
+                    self.target_turn = None;
                     self.need_redraw = true;
-                    self.last_frame = Instant::now();
                 }
             }
-            // TODO
+            let play_interval = Duration::new(0, ((1.0 / (2.0 * self.play_speed)) * 1e9) as u32);
+            if self.playing && self.last_frame.elapsed() >= play_interval
+                && self.turn + 1 < self.game_data.num_turns() {
+                self.turn += 1;
+                self.need_redraw = true;
+                self.last_frame = Instant::now();
+            }
+            // tiles slide towards the next turn in every frame until it is reached
+            let mut progress = 0.0;
+            if self.smooth && self.playing && self.turn + 1 < self.game_data.num_turns() {
+                let elapsed = self.last_frame.elapsed().as_secs_f32();
+                progress = (elapsed / play_interval.as_secs_f32()).min(1.0);
+                self.need_redraw = true;
+            }
             if self.need_redraw {
-                self.graphics.draw_turn(&self.game_data, self.turn, &self.display);
+                self.center_on_selection();
+                self.graphics.update_view(self.game_data.num_rows(), self.game_data.num_cols());
+                self.update_overlay();
+                self.graphics.draw_turn(
+                    &self.game_data,
+                    self.turn,
+                    progress,
+                    &self.overlay,
+                    &self.display,
+                );
                 self.need_redraw = false;
             }
             thread::sleep(Duration::new(0, 16000000));
         }
+        Ok(())
     }
 
     fn handle_events(&mut self) {
@@ -80,12 +236,37 @@ impl Insight {
         let graphics = &mut self.graphics;
         let turn = &mut self.turn;
         let num_turns = self.game_data.num_turns();
+        let num_rows = self.game_data.num_rows();
+        let num_cols = self.game_data.num_cols();
         let need_redraw = &mut self.need_redraw;
         let playing = &mut self.playing;
         let play_speed = &mut self.play_speed;
+        let layer_bank = &mut self.layer_bank;
+        let show_log = &mut self.show_log;
+        let cursor = &mut self.cursor;
+        let scrolling = &mut self.scrolling;
+        let panning = &mut self.panning;
+        let scrubbing = &mut self.scrubbing;
+        let show_timeline = &mut self.show_timeline;
+        let bookmarks = &mut self.bookmarks;
+        let goto_input = &mut self.goto_input;
+        let target_turn = &mut self.target_turn;
+        let show_hud = &mut self.show_hud;
+        let smooth = &mut self.smooth;
+        let press = &mut self.press;
+        let selection = &mut self.selection;
+        let follow_selection = &mut self.follow_selection;
+        let centered_on = &mut self.centered_on;
+        let game_data = &self.game_data;
+        let track = if *show_timeline {
+            Some(Track::new(graphics.width() as f32, graphics.height() as f32, num_turns))
+        } else {
+            None
+        };
+        let display = &self.display;
         self.events_loop.poll_events(|ev| {
-            match ev {
-                WindowEvent { event: wev, .. } => match wev {
+            if let WindowEvent { event: wev, .. } = ev {
+                match wev {
                     WE::Resized(width, height) => {
                         graphics.set_view_port(width, height);
                         *need_redraw = true;
@@ -93,13 +274,41 @@ impl Insight {
                     WE::Closed => *closing = true,
                     WE::KeyboardInput { input, .. } => {
                         if let ElementState::Pressed = input.state {
+                            if let Some(ref mut digits) = *goto_input {
+                                // the digits themselves arrive as characters
+                                match input.virtual_keycode {
+                                    Some(VirtualKeyCode::Return)
+                                        | Some(VirtualKeyCode::NumpadEnter) => {
+                                        if let Ok(goto_turn) = digits.parse::<u32>() {
+                                            *playing = false;
+                                            *target_turn = Some(goto_turn);
+                                        }
+                                        *goto_input = None;
+                                    },
+                                    Some(VirtualKeyCode::Escape) => *goto_input = None,
+                                    Some(VirtualKeyCode::Back) => {
+                                        digits.pop();
+                                    },
+                                    _ => (),
+                                }
+                                *need_redraw = true;
+                                return;
+                            }
+                            if input.modifiers.shift {
+                                let direction = input.virtual_keycode.and_then(arrow_direction);
+                                if let Some((right, down)) = direction {
+                                    graphics.scroll(right, down, num_rows, num_cols);
+                                    *need_redraw = true;
+                                    return;
+                                }
+                            }
                             match input.virtual_keycode {
                                 Some(VirtualKeyCode::Q) => {
                                     *closing = true
                                 },
                                 Some(VirtualKeyCode::Right) => {
                                     *playing = false;
-                                    if *turn < num_turns - 1 {
+                                    if *turn + 1 < num_turns {
                                         *turn += 1;
                                         *need_redraw = true;
                                     }
@@ -118,71 +327,600 @@ impl Insight {
                                 },
                                 Some(VirtualKeyCode::End) => {
                                     *playing = false;
-                                    *turn = num_turns - 1;
+                                    *turn = num_turns.saturating_sub(1);
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Down) => {
-                                    if *play_speed > 0.2 {
-                                        *play_speed -= 0.2;
-                                    }
+                                Some(VirtualKeyCode::Down) if *play_speed > 0.2 => {
+                                    *play_speed -= 0.2;
+                                    *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Up) => {
-                                    if *play_speed < 50.0 {
-                                        *play_speed += 0.2;
-                                    }
+                                Some(VirtualKeyCode::Up) if *play_speed < 50.0 => {
+                                    *play_speed += 0.2;
+                                    *need_redraw = true;
                                 },
                                 Some(VirtualKeyCode::Space) => {
                                     *playing = !*playing;
+                                    *need_redraw = true;
+                                },
+                                Some(VirtualKeyCode::H) => {
+                                    *show_hud = !*show_hud;
+                                    *need_redraw = true;
+                                },
+                                Some(VirtualKeyCode::F) if selection.is_some() => {
+                                    *follow_selection = !*follow_selection;
+                                    *centered_on = None;
+                                    *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key0) => {
-                                    graphics.toggle_layer(9);
+                                Some(VirtualKeyCode::Escape) => {
+                                    *selection = None;
+                                    *follow_selection = false;
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key1) => {
-                                    graphics.toggle_layer(0);
+                                Some(VirtualKeyCode::I) => {
+                                    *smooth = !*smooth;
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key2) => {
-                                    graphics.toggle_layer(1);
+                                Some(VirtualKeyCode::L) => {
+                                    *show_log = !*show_log;
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key3) => {
-                                    graphics.toggle_layer(2);
+                                Some(VirtualKeyCode::G) => {
+                                    *goto_input = Some(String::new());
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key4) => {
-                                    graphics.toggle_layer(3);
+                                Some(VirtualKeyCode::T) => {
+                                    *show_timeline = !*show_timeline;
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key5) => {
-                                    graphics.toggle_layer(4);
+                                Some(VirtualKeyCode::B) => {
+                                    if !bookmarks.remove(turn) {
+                                        bookmarks.insert(*turn);
+                                    }
+                                    *need_redraw = true;
+                                },
+                                Some(VirtualKeyCode::W) => {
+                                    graphics.pan(0.0, PAN_STEP);
+                                    *need_redraw = true;
+                                },
+                                Some(VirtualKeyCode::A) => {
+                                    graphics.pan(PAN_STEP, 0.0);
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key6) => {
-                                    graphics.toggle_layer(5);
+                                Some(VirtualKeyCode::S) => {
+                                    graphics.pan(0.0, -PAN_STEP);
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key7) => {
-                                    graphics.toggle_layer(6);
+                                Some(VirtualKeyCode::D) => {
+                                    graphics.pan(-PAN_STEP, 0.0);
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key8) => {
-                                    graphics.toggle_layer(7);
+                                Some(VirtualKeyCode::Equals) | Some(VirtualKeyCode::Add) => {
+                                    let (x, y) = graphics.center();
+                                    graphics.zoom(ZOOM_STEP, x, y);
                                     *need_redraw = true;
                                 },
-                                Some(VirtualKeyCode::Key9) => {
-                                    graphics.toggle_layer(8);
+                                Some(VirtualKeyCode::Minus)
+                                    | Some(VirtualKeyCode::Subtract) => {
+                                    let (x, y) = graphics.center();
+                                    graphics.zoom(1.0 / ZOOM_STEP, x, y);
+                                    *need_redraw = true;
+                                },
+                                Some(VirtualKeyCode::R) => {
+                                    graphics.reset_view();
+                                    *need_redraw = true;
+                                },
+                                Some(VirtualKeyCode::LBracket) if *layer_bank > 0 => {
+                                    *layer_bank -= 1;
+                                    set_layer_bank_title(display, *layer_bank);
+                                    *need_redraw = true;
+                                },
+                                Some(VirtualKeyCode::RBracket)
+                                    if *layer_bank < u32::MAX / 10 - 1 => {
+                                    *layer_bank += 1;
+                                    set_layer_bank_title(display, *layer_bank);
+                                    *need_redraw = true;
+                                },
+                                Some(key) => if let Some(digit) = layer_digit(key) {
+                                    graphics.toggle_layer(*layer_bank * 10 + digit);
                                     *need_redraw = true;
                                 },
                                 _ => (),
                             }
                         }
                     },
+                    WE::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        match (track, *cursor) {
+                            (Some(track), Some((x, y))) if track.contains(x, y) => {
+                                *scrubbing = true;
+                                *playing = false;
+                                *turn = track.turn_at(x, num_turns);
+                                *need_redraw = true;
+                            },
+                            _ => {
+                                *panning = true;
+                                *press = *cursor;
+                            },
+                        }
+                    },
+                    WE::MouseInput {
+                        state: ElementState::Released,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        *scrubbing = false;
+                        *panning = false;
+                        if let (Some((press_x, press_y)), Some((x, y))) = (press.take(), *cursor) {
+                            let distance = (x - press_x).hypot(y - press_y);
+                            if distance <= CLICK_DISTANCE && *turn < num_turns {
+                                // clicking a cell without entities clears the selection
+                                *selection = graphics.screen_to_cell(x, y, num_rows, num_cols)
+                                    .and_then(|(row, col)| {
+                                        game_data.cell_tiles(*turn, row, col).into_iter()
+                                            .find_map(|(_, tile)| tile.entity)
+                                    });
+                                if selection.is_none() {
+                                    *follow_selection = false;
+                                }
+                                *need_redraw = true;
+                            }
+                        }
+                    },
+                    WE::MouseInput { state, button: MouseButton::Right, .. } => {
+                        *scrolling = state == ElementState::Pressed;
+                    },
+                    WE::MouseWheel { delta, .. } => if let Some((x, y)) = *cursor {
+                        let steps = match delta {
+                            MouseScrollDelta::LineDelta(_, lines) => lines,
+                            MouseScrollDelta::PixelDelta(_, pixels) => pixels / 40.0,
+                        };
+                        graphics.zoom(ZOOM_STEP.powf(steps), x, y);
+                        *need_redraw = true;
+                    },
+                    WE::CursorMoved { position: (x, y), .. } => {
+                        let (x, y) = (x as f32, y as f32);
+                        if let (true, Some(track)) = (*scrubbing, track) {
+                            *turn = track.turn_at(x, num_turns);
+                        } else if let Some((last_x, last_y)) = *cursor {
+                            // drag the board along with the cursor
+                            if *scrolling {
+                                graphics.scroll_pixels(last_x - x, last_y - y, num_rows, num_cols);
+                            } else if *panning {
+                                graphics.pan(x - last_x, y - last_y);
+                            }
+                        }
+                        *cursor = Some((x, y));
+                        *need_redraw = true;
+                    },
+                    WE::ReceivedCharacter(character) => {
+                        if let Some(ref mut digits) = *goto_input {
+                            if character.is_ascii_digit() && digits.len() < 9 {
+                                digits.push(character);
+                                *need_redraw = true;
+                            }
+                        }
+                    },
+                    WE::CursorLeft { .. } => {
+                        *cursor = None;
+                        *need_redraw = true;
+                    },
                     WE::Focused(true) => *need_redraw = true,
                     _ => (),
-                },
-                _ => (),
+                }
             }
         });
     }
-}
\ No newline at end of file
+
+    fn update_overlay(&mut self) {
+        self.overlay.clear();
+        if self.turn < self.game_data.num_turns() {
+            self.draw_selection();
+            self.draw_heat_legends();
+        }
+        if self.show_hud {
+            self.draw_hud();
+        }
+        if self.show_log {
+            self.draw_log();
+        }
+        if self.show_timeline {
+            self.draw_timeline();
+        }
+        if self.turn < self.game_data.num_turns() {
+            self.draw_tooltip();
+        }
+        if self.goto_input.is_some() {
+            self.draw_goto_prompt();
+        }
+    }
+
+    /// Moves the view to the selected entity when following it and it has not been centered
+    /// on in this turn yet, so the board can still be panned until the next turn.
+    fn center_on_selection(&mut self) {
+        let id = match self.selection {
+            Some(id) if self.follow_selection => id,
+            _ => return,
+        };
+        if self.centered_on == Some((id, self.turn)) {
+            return;
+        }
+        if let Some(waypoint) = self.game_data.entity_position(id, self.turn) {
+            self.graphics.follow(
+                waypoint.row,
+                waypoint.col,
+                self.game_data.num_rows(),
+                self.game_data.num_cols(),
+                self.game_data.wraps(),
+            );
+        }
+        self.centered_on = Some((id, self.turn));
+    }
+
+    /// Draws an outline around the selected entity and a trail of dots where it was in the
+    /// previous turns, fading out with age.
+    fn draw_selection(&mut self) {
+        let id = match self.selection {
+            Some(id) => id,
+            None => return,
+        };
+        let num_rows = self.game_data.num_rows();
+        let num_cols = self.game_data.num_cols();
+        let cell_size = self.graphics.cell_size();
+        let dot_size = (cell_size * 0.3).max(3.0);
+        let first_turn = self.turn.saturating_sub(TRAIL_LENGTH);
+        for waypoint in self.game_data.trajectory(id) {
+            if waypoint.turn < first_turn || waypoint.turn >= self.turn {
+                continue;
+            }
+            let (x, y) = self.graphics.cell_center(waypoint.row, waypoint.col, num_rows, num_cols);
+            let age = (self.turn - waypoint.turn) as f32 / (TRAIL_LENGTH + 1) as f32;
+            let mut color = SELECTION_COLOR;
+            color[3] *= 1.0 - age;
+            self.overlay.rect(x - dot_size / 2.0, y - dot_size / 2.0, dot_size, dot_size, color);
+        }
+        if let Some(waypoint) = self.game_data.entity_position(id, self.turn) {
+            let (x, y) = self.graphics.cell_center(waypoint.row, waypoint.col, num_rows, num_cols);
+            let left = x - cell_size / 2.0 - OUTLINE_WIDTH;
+            let top = y - cell_size / 2.0 - OUTLINE_WIDTH;
+            let size = cell_size + 2.0 * OUTLINE_WIDTH;
+            let right = left + size - OUTLINE_WIDTH;
+            let bottom = top + size - OUTLINE_WIDTH;
+            self.overlay.rect(left, top, size, OUTLINE_WIDTH, SELECTION_COLOR);
+            self.overlay.rect(left, bottom, size, OUTLINE_WIDTH, SELECTION_COLOR);
+            self.overlay.rect(left, top, OUTLINE_WIDTH, size, SELECTION_COLOR);
+            self.overlay.rect(right, top, OUTLINE_WIDTH, size, SELECTION_COLOR);
+        }
+    }
+
+    /// Draws the turn typed so far in the middle of the window.
+    fn draw_goto_prompt(&mut self) {
+        let title = "Go to turn (Enter to jump, Escape to cancel)";
+        let digits = self.goto_input.as_ref().map_or("", |digits| digits.as_str());
+        let lines = [format!("> {}_", digits)];
+        let (width, height) = overlay::panel_size(TEXT_SCALE, title, &lines);
+        let (center_x, center_y) = self.graphics.center();
+        let x = (center_x - width / 2.0).max(0.0);
+        let y = (center_y - height / 2.0).max(0.0);
+        self.overlay.panel(x, y, TEXT_SCALE, title, &lines);
+    }
+
+    /// Draws the timeline with the current turn, turns with messages or labels and bookmarks.
+    fn draw_timeline(&mut self) {
+        let screen_width = self.graphics.width() as f32;
+        let screen_height = self.graphics.height() as f32;
+        let num_turns = self.game_data.num_turns();
+        let track = Track::new(screen_width, screen_height, num_turns);
+        let top = screen_height - timeline_height();
+        self.overlay.rect(0.0, top, screen_width, timeline_height(), PANEL_COLOR);
+        let counter = turn_counter(self.turn, num_turns);
+        self.overlay.text(PANEL_PADDING, top + PANEL_PADDING, TEXT_SCALE, TEXT_COLOR, &counter);
+
+        self.overlay.rect(track.x, track.y, track.width, track.height, TRACK_COLOR);
+        if num_turns == 0 {
+            return;
+        }
+        let progress = track.position(self.turn + 1, num_turns) - track.x;
+        self.overlay.rect(track.x, track.y, progress, track.height, PROGRESS_COLOR);
+        // only draw one annotation marker per pixel, there may be many more turns than pixels
+        let mut last_marker = None;
+        for turn in 0..num_turns {
+            if !self.game_data.is_annotated(turn) {
+                continue;
+            }
+            let x = track.position(turn, num_turns).floor();
+            if last_marker != Some(x) {
+                self.overlay.rect(x, track.y, 2.0, track.height / 2.0, ANNOTATION_COLOR);
+                last_marker = Some(x);
+            }
+        }
+        for &turn in self.bookmarks.range(..num_turns) {
+            let x = track.position(turn, num_turns).floor();
+            self.overlay.rect(x, track.y, 2.0, track.height, BOOKMARK_COLOR);
+        }
+        let x = track.position(self.turn, num_turns).floor();
+        self.overlay.rect(x - 1.0, track.y - 3.0, 3.0, track.height + 6.0, HANDLE_COLOR);
+    }
+
+    /// Draws the state of the viewer into the top left corner, so screenshots tell what they
+    /// show.
+    fn draw_hud(&mut self) {
+        let max_width = (self.graphics.width() as f32 / 3.0)
+            .min(overlay::chars_width(LOG_WIDTH, TEXT_SCALE));
+        let max_chars = overlay::max_chars(max_width - 2.0 * PANEL_PADDING, TEXT_SCALE);
+        let title = format!("{}, turn {}", self.game_data.file_name(), self.turn);
+        let loading = if self.game_data.is_complete() { "" } else { ", loading" };
+        let mut lines = vec![
+            format!("{} turns{}", self.game_data.num_turns(), loading),
+            format!(
+                "{} at speed {:.1} ({:.1} turns/s{})",
+                if self.playing { "playing" } else { "paused" },
+                self.play_speed,
+                2.0 * self.play_speed,
+                if self.smooth { ", smooth" } else { "" },
+            ),
+            format!(
+                "number keys toggle layers {} to {}",
+                self.layer_bank * 10,
+                self.layer_bank * 10 + 9,
+            ),
+        ];
+        if let Some(id) = self.selection {
+            let position = self.game_data.entity_position(id, self.turn);
+            lines.push(format!(
+                "entity {} selected{}{}",
+                id,
+                if self.follow_selection { ", following" } else { "" },
+                if position.is_none() { ", not in this turn" } else { "" },
+            ));
+        }
+        let hidden_layers = self.graphics.hidden_layers();
+        if hidden_layers.is_empty() {
+            lines.push("all layers visible".to_string());
+        } else {
+            let hidden_layers = hidden_layers.iter()
+                .map(|&layer| match self.game_data.layer_name(layer) {
+                    Some(name) => format!("{} ({})", layer, name),
+                    None => layer.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            lines.extend(overlay::wrap(&format!("hidden layers: {}", hidden_layers), max_chars));
+        }
+        self.overlay.panel(0.0, 0.0, TEXT_SCALE, &title, &lines);
+    }
+
+    /// Draws the messages of the current turn into a panel on the right side of the window.
+    fn draw_log(&mut self) {
+        let screen_width = self.graphics.width() as f32;
+        let screen_height = self.graphics.height() as f32;
+        let max_width = (screen_width / 3.0).min(overlay::chars_width(LOG_WIDTH, TEXT_SCALE));
+        let max_chars = overlay::max_chars(max_width - 2.0 * PANEL_PADDING, TEXT_SCALE);
+        let max_lines = ((screen_height - 2.0 * PANEL_PADDING)
+            / overlay::line_height(TEXT_SCALE)) as usize;
+
+        let mut lines = Vec::new();
+        if self.turn < self.game_data.num_turns() {
+            for message in self.game_data.messages(self.turn) {
+                if let Some(layer) = message.layer {
+                    if !self.graphics.is_layer_visible(layer) {
+                        continue;
+                    }
+                }
+                let text = match message.cell {
+                    Some((row, col)) => format!("({}, {}) {}", row, col, message.text),
+                    None => message.text,
+                };
+                lines.extend(overlay::wrap(&text, max_chars));
+            }
+        }
+        if lines.is_empty() {
+            lines.push("no messages".to_string());
+        }
+        lines.truncate(max_lines.saturating_sub(1));
+
+        let title = format!("Log (turn {})", self.turn);
+        let (width, _) = overlay::panel_size(TEXT_SCALE, &title, &lines);
+        self.overlay.panel(screen_width - width, 0.0, TEXT_SCALE, &title, &lines);
+    }
+
+    /// Draws a color scale for each visible heat map, stacked up from the bottom left corner.
+    fn draw_heat_legends(&mut self) {
+        let line_height = overlay::line_height(TEXT_SCALE);
+        let height = 2.0 * line_height + LEGEND_HEIGHT + 2.0 * PANEL_PADDING;
+        let width = LEGEND_WIDTH + 2.0 * PANEL_PADDING;
+        let mut y = self.graphics.height() as f32;
+        if self.show_timeline {
+            y -= timeline_height();
+        }
+        for heat_map in self.game_data.heat_maps(self.turn) {
+            if !self.graphics.is_layer_visible(heat_map.layer) {
+                continue;
+            }
+            let heat_scale = self.game_data.heat_scale(heat_map.layer);
+            let (min, max) = heat_scale.bounds(&heat_map);
+            y -= height;
+            let x = PANEL_PADDING;
+            self.overlay.rect(0.0, y, width, height, PANEL_COLOR);
+            let title = layer_title(&self.game_data, heat_map.layer);
+            self.overlay.text(x, y + PANEL_PADDING, TEXT_SCALE, TITLE_COLOR, &title);
+            let bar_y = y + PANEL_PADDING + line_height;
+            let step_width = LEGEND_WIDTH / LEGEND_STEPS as f32;
+            for step in 0..LEGEND_STEPS {
+                let position = (step as f32 + 0.5) / LEGEND_STEPS as f32;
+                let color = heat_scale.colormap.color(position).extend(1.0).into();
+                let step_x = x + step as f32 * step_width;
+                self.overlay.rect(step_x, bar_y, step_width, LEGEND_HEIGHT, color);
+            }
+            let text_y = bar_y + LEGEND_HEIGHT + 4.0;
+            let max_text = format_value(max);
+            let max_x = x + LEGEND_WIDTH - overlay::text_width(&max_text, TEXT_SCALE);
+            self.overlay.text(x, text_y, TEXT_SCALE, TEXT_COLOR, &format_value(min));
+            self.overlay.text(max_x, text_y, TEXT_SCALE, TEXT_COLOR, &max_text);
+        }
+    }
+
+    /// Lists everything in the cell under the mouse cursor next to it, including the contents
+    /// of hidden layers.
+    fn draw_tooltip(&mut self) {
+        let (x, y) = match self.cursor {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        let (row, col) = match self.graphics.screen_to_cell(
+            x,
+            y,
+            self.game_data.num_rows(),
+            self.game_data.num_cols(),
+        ) {
+            Some(cell) => cell,
+            None => return,
+        };
+        let mut lines = Vec::new();
+        for (symbol, tile) in self.game_data.cell_tiles(self.turn, row, col) {
+            let name = match tile.entity {
+                Some(id) => format!("entity {} ({})", id, symbol),
+                None => format!("tile {}", symbol),
+            };
+            lines.push(format!(
+                "{}: {} {}, {}{}",
+                name,
+                tile.shape.name(),
+                format_color(tile.color),
+                layer_title(&self.game_data, tile.layer),
+                self.hidden_note(tile.layer),
+            ));
+        }
+        for line in self.game_data.lines(self.turn) {
+            let starts_here = (line.r1, line.c1) == (row, col);
+            let ends_here = (line.r2, line.c2) == (row, col);
+            if !starts_here && !ends_here {
+                continue;
+            }
+            lines.push(format!(
+                "line ({}, {}) to ({}, {}): {}, {}{}",
+                line.r1,
+                line.c1,
+                line.r2,
+                line.c2,
+                format_color(line.color),
+                layer_title(&self.game_data, line.layer),
+                self.hidden_note(line.layer),
+            ));
+        }
+        for label in self.game_data.labels(self.turn) {
+            if (label.row, label.col) == (row, col) {
+                lines.push(format!(
+                    "label \"{}\", {}{}",
+                    label.text,
+                    layer_title(&self.game_data, label.layer),
+                    self.hidden_note(label.layer),
+                ));
+            }
+        }
+        for heat_map in self.game_data.heat_maps(self.turn) {
+            if let Some(value) = heat_map.value(row, col) {
+                lines.push(format!(
+                    "heat {}, {}{}",
+                    format_value(value),
+                    layer_title(&self.game_data, heat_map.layer),
+                    self.hidden_note(heat_map.layer),
+                ));
+            }
+        }
+        if lines.is_empty() {
+            lines.push("empty".to_string());
+        }
+        let max_lines = ((self.graphics.height() as f32 - 2.0 * PANEL_PADDING)
+            / overlay::line_height(TEXT_SCALE)) as usize;
+        if lines.len() + 1 > max_lines {
+            lines.truncate(max_lines.saturating_sub(2));
+            lines.push("...".to_string());
+        }
+        let title = format!("({}, {})", row, col);
+        let (width, height) = overlay::panel_size(TEXT_SCALE, &title, &lines);
+        let x = (x + 16.0).min(self.graphics.width() as f32 - width).max(0.0);
+        let y = (y + 16.0).min(self.graphics.height() as f32 - height).max(0.0);
+        self.overlay.panel(x, y, TEXT_SCALE, &title, &lines);
+    }
+
+    fn hidden_note(&self, layer: u32) -> &'static str {
+        if self.graphics.is_layer_visible(layer) { "" } else { " (hidden)" }
+    }
+}
+
+fn timeline_height() -> f32 {
+    overlay::line_height(TEXT_SCALE) + 2.0 * PANEL_PADDING
+}
+
+fn turn_counter(turn: u32, num_turns: u32) -> String {
+    format!("{} / {}", turn, num_turns)
+}
+
+/// The name of a layer, or its number if it has none.
+fn layer_title(game_data: &GameData, layer: u32) -> String {
+    match game_data.layer_name(layer) {
+        Some(name) => name.to_string(),
+        None => format!("layer {}", layer),
+    }
+}
+
+fn format_color(color: Vector4<f32>) -> String {
+    format!(
+        "rgba({}, {}, {}, {})",
+        format_value(color.x),
+        format_value(color.y),
+        format_value(color.z),
+        format_value(color.w),
+    )
+}
+
+/// Formats a number with a few significant digits.
+fn format_value(value: f32) -> String {
+    if value != 0.0 && (value.abs() >= 1e5 || value.abs() < 1e-3) {
+        format!("{:.3e}", value)
+    } else {
+        let text = format!("{:.3}", value);
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// Maps the number keys to the layers of a bank, with 1 being the first and 0 the last.
+fn layer_digit(key: VirtualKeyCode) -> Option<u32> {
+    match key {
+        VirtualKeyCode::Key1 => Some(0),
+        VirtualKeyCode::Key2 => Some(1),
+        VirtualKeyCode::Key3 => Some(2),
+        VirtualKeyCode::Key4 => Some(3),
+        VirtualKeyCode::Key5 => Some(4),
+        VirtualKeyCode::Key6 => Some(5),
+        VirtualKeyCode::Key7 => Some(6),
+        VirtualKeyCode::Key8 => Some(7),
+        VirtualKeyCode::Key9 => Some(8),
+        VirtualKeyCode::Key0 => Some(9),
+        _ => None,
+    }
+}
+
+/// Maps the arrow keys to the direction they scroll the board in, as cells to the right and
+/// down.
+fn arrow_direction(key: VirtualKeyCode) -> Option<(f32, f32)> {
+    match key {
+        VirtualKeyCode::Right => Some((1.0, 0.0)),
+        VirtualKeyCode::Left => Some((-1.0, 0.0)),
+        VirtualKeyCode::Down => Some((0.0, 1.0)),
+        VirtualKeyCode::Up => Some((0.0, -1.0)),
+        _ => None,
+    }
+}
+
+fn set_layer_bank_title(display: &Display, layer_bank: u32) {
+    let first_layer = layer_bank * 10;
+    display.gl_window().set_title(&format!(
+        "Ants Insight (number keys toggle layers {} to {})",
+        first_layer,
+        first_layer + 9,
+    ));
+}
@@ -0,0 +1,36 @@
+use std::collections::HashSet;
+
+/// Which layers are toggled off, shared by everything that draws the board.
+#[derive(Clone, Default)]
+pub struct LayerVisibility {
+    hidden_layers: HashSet<u32>,
+}
+
+impl LayerVisibility {
+    pub fn new() -> LayerVisibility {
+        LayerVisibility {
+            hidden_layers: HashSet::new(),
+        }
+    }
+
+    pub fn toggle(&mut self, layer: u32) {
+        if !self.hidden_layers.remove(&layer) {
+            self.hidden_layers.insert(layer);
+        }
+    }
+
+    pub fn hide(&mut self, layer: u32) {
+        self.hidden_layers.insert(layer);
+    }
+
+    pub fn is_visible(&self, layer: u32) -> bool {
+        !self.hidden_layers.contains(&layer)
+    }
+
+    /// The layers that are toggled off, in ascending order.
+    pub fn hidden(&self) -> Vec<u32> {
+        let mut hidden_layers: Vec<u32> = self.hidden_layers.iter().cloned().collect();
+        hidden_layers.sort();
+        hidden_layers
+    }
+}
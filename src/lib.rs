@@ -0,0 +1,50 @@
+//! Parser, data model and viewer for insight files, the debug output of Ants bots.
+
+#[macro_use] extern crate glium;
+extern crate cgmath;
+extern crate png;
+extern crate gif;
+
+mod insight;
+mod graphics;
+mod game_data;
+mod parser;
+mod scanner;
+mod turn_cache;
+mod writer;
+mod font;
+mod overlay;
+mod colormap;
+mod torus;
+mod camera;
+mod layers;
+mod view;
+mod render;
+mod export;
+mod svg;
+mod tween;
+
+pub use game_data::GameData;
+pub use game_data::Tile;
+pub use game_data::Waypoint;
+pub use game_data::Line;
+pub use game_data::Shape;
+pub use game_data::TileIterator;
+pub use game_data::LineIterator;
+pub use game_data::Message;
+pub use game_data::MessageIterator;
+pub use game_data::Label;
+pub use game_data::LabelIterator;
+pub use game_data::HeatMap;
+pub use game_data::HeatMapIterator;
+pub use game_data::HeatScale;
+pub use colormap::Colormap;
+pub use parser::ParseError;
+pub use insight::Insight;
+pub use writer::InsightWriter;
+pub use layers::LayerVisibility;
+pub use view::View;
+pub use render::Renderer;
+pub use export::export_gif;
+pub use export::export_png_sequence;
+pub use svg::write_svg;
@@ -1,15 +1,333 @@
-#[macro_use] extern crate glium;
-extern crate cgmath;
+extern crate ants_insight;
 
-mod insight;
-mod graphics;
-mod game_data;
-
-use insight::Insight;
+use ants_insight::Insight;
+use ants_insight::GameData;
+use ants_insight::Renderer;
+use ants_insight::LayerVisibility;
+use ants_insight::write_svg;
+use ants_insight::export_gif;
+use ants_insight::export_png_sequence;
 use std::env;
+use std::fmt::Display;
+use std::io;
+use std::io::BufReader;
+use std::io::IsTerminal;
+use std::process;
+
+fn usage() -> ! {
+    eprintln!("Usage: ants_insight [-f|--follow] [-w|--wrap] [--turn <n>] [<file>|-]");
+    eprintln!("       ants_insight render [<render options>] [--turn <n>] -o <png file> <file>");
+    eprintln!("       ants_insight export [<render options>] [--from <n>] [--to <n>] [--fps <n>]");
+    eprintln!("                           -o <gif or png file> <file>");
+    eprintln!("       ants_insight svg [-w|--wrap] [--hide <layer>,...] [--turn <n>]");
+    eprintln!("                        -o <svg file> <file>");
+    eprintln!("Reads from stdin if the file is - or missing and stdin is not a terminal.");
+    eprintln!("Render options: [-w|--wrap] [--size <width>x<height>] [--hide <layer>,...]");
+    eprintln!("                [--zoom <factor>] [--center <row>,<col>]");
+    eprintln!("Image sizes are limited to 65535 pixels on each side.");
+    eprintln!("Exporting to a png file writes one file per turn, numbered by turn.");
+    process::exit(2);
+}
+
+fn exit_with_error<E: Display>(error: E) -> ! {
+    eprintln!("error: {}", error);
+    process::exit(1);
+}
+
+/// Parses a size like 1024x768, up to the largest size a GIF can have.
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.splitn(2, 'x');
+    let width = parts.next()?.parse::<u32>().ok()?;
+    let height = parts.next()?.parse::<u32>().ok()?;
+    let max_size = u32::from(u16::MAX);
+    if width == 0 || height == 0 || width > max_size || height > max_size {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// Parses a comma separated list of layers.
+fn parse_layers(layers: &str) -> Option<Vec<u32>> {
+    layers.split(',').map(|layer| layer.parse::<u32>().ok()).collect()
+}
+
+/// Parses a cell like 12,40.
+fn parse_cell(cell: &str) -> Option<(u32, u32)> {
+    let mut parts = cell.splitn(2, ',');
+    let row = parts.next()?.parse::<u32>().ok()?;
+    let col = parts.next()?.parse::<u32>().ok()?;
+    Some((row, col))
+}
+
+fn parse_turn(turn: Option<String>) -> u32 {
+    match turn.and_then(|turn| turn.parse::<u32>().ok()) {
+        Some(turn) => turn,
+        None => usage(),
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Command {
+    Render,
+    Export,
+    Svg,
+}
+
+/// The options of the render, export and svg commands.
+struct RenderOptions {
+    file_name: String,
+    output: String,
+    wrap: bool,
+    size: (u32, u32),
+    hidden_layers: Vec<u32>,
+    zoom: f32,
+    center: Option<(u32, u32)>,
+    turn: Option<u32>,
+    from: Option<u32>,
+    to: Option<u32>,
+    fps: f32,
+}
+
+impl RenderOptions {
+    /// Parses the arguments after the command, only accepting the options the command uses.
+    fn parse<I: Iterator<Item = String>>(mut args: I, command: Command) -> RenderOptions {
+        let raster = command != Command::Svg;
+        let export = command == Command::Export;
+        let mut file_name = None;
+        let mut output = None;
+        let mut options = RenderOptions {
+            file_name: String::new(),
+            output: String::new(),
+            wrap: false,
+            size: (1024, 1024),
+            hidden_layers: Vec::new(),
+            zoom: 1.0,
+            center: None,
+            turn: None,
+            from: None,
+            to: None,
+            fps: 10.0,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-w" | "--wrap" => options.wrap = true,
+                "--size" if raster => match args.next().and_then(|size| parse_size(&size)) {
+                    Some(size) => options.size = size,
+                    None => usage(),
+                },
+                "--hide" => match args.next().and_then(|layers| parse_layers(&layers)) {
+                    Some(layers) => options.hidden_layers.extend(layers),
+                    None => usage(),
+                },
+                "--zoom" if raster => match args.next().and_then(|zoom| zoom.parse::<f32>().ok()) {
+                    Some(zoom) if zoom > 0.0 => options.zoom = zoom,
+                    _ => usage(),
+                },
+                "--center" if raster => match args.next().and_then(|cell| parse_cell(&cell)) {
+                    Some(cell) => options.center = Some(cell),
+                    None => usage(),
+                },
+                "-o" | "--output" => match args.next() {
+                    Some(output_name) => output = Some(output_name),
+                    None => usage(),
+                },
+                "--turn" if !export => options.turn = Some(parse_turn(args.next())),
+                "--from" if export => options.from = Some(parse_turn(args.next())),
+                "--to" if export => options.to = Some(parse_turn(args.next())),
+                "--fps" if export => match args.next().and_then(|fps| fps.parse::<f32>().ok()) {
+                    Some(fps) if fps > 0.0 => options.fps = fps,
+                    _ => usage(),
+                },
+                _ if file_name.is_none() => file_name = Some(arg),
+                _ => usage(),
+            }
+        }
+        match (file_name, output) {
+            (Some(file_name), Some(output)) => {
+                options.file_name = file_name;
+                options.output = output;
+            },
+            _ => usage(),
+        }
+        options
+    }
+
+    /// Loads the whole game, from stdin if the file is -, exiting on errors.
+    fn load_game(&self) -> GameData {
+        let game_data = if self.file_name == "-" {
+            GameData::from_reader(BufReader::new(io::stdin()), "<stdin>")
+        } else {
+            GameData::load(&self.file_name, false)
+        };
+        let mut game_data = match game_data {
+            Ok(game_data) => game_data,
+            Err(error) => exit_with_error(error),
+        };
+        if let Err(error) = game_data.wait() {
+            exit_with_error(error);
+        }
+        if self.wrap {
+            game_data.set_wrap(true);
+        }
+        game_data
+    }
+
+    /// Exits unless the turn exists.
+    fn check_turn(&self, game_data: &GameData, turn: u32) {
+        if turn >= game_data.num_turns() {
+            exit_with_error(format!(
+                "there is no turn {} in {}, which has {} turns",
+                turn,
+                game_data.file_name(),
+                game_data.num_turns(),
+            ));
+        }
+    }
+
+    /// A renderer with the layers and the camera set up as requested.
+    fn renderer(&self, game_data: &GameData) -> Renderer {
+        let mut renderer = Renderer::new(self.size.0, self.size.1);
+        for &layer in &self.hidden_layers {
+            renderer.layers_mut().hide(layer);
+        }
+        let num_rows = game_data.num_rows();
+        let num_cols = game_data.num_cols();
+        let view = renderer.view_mut();
+        if let Some((row, col)) = self.center {
+            if row >= num_rows || col >= num_cols {
+                exit_with_error(format!(
+                    "there is no cell {},{} on the board of {} rows and {} columns",
+                    row,
+                    col,
+                    num_rows,
+                    num_cols,
+                ));
+            }
+            view.follow(row, col, num_rows, num_cols, game_data.wraps());
+        }
+        let (center_x, center_y) = view.center();
+        view.zoom(self.zoom, center_x, center_y);
+        renderer
+    }
+}
+
+/// Exits if a turn could not be parsed again while drawing, because the file has changed.
+fn check_turns_read(game_data: &mut GameData) {
+    if let Err(error) = game_data.update() {
+        exit_with_error(error);
+    }
+}
+
+/// Draws a single turn into a PNG file without opening a window.
+fn render<I: Iterator<Item = String>>(args: I) {
+    let options = RenderOptions::parse(args, Command::Render);
+    let mut game_data = options.load_game();
+    let turn = options.turn.unwrap_or(0);
+    options.check_turn(&game_data, turn);
+    let mut renderer = options.renderer(&game_data);
+    renderer.render(&game_data, turn);
+    check_turns_read(&mut game_data);
+    if let Err(error) = renderer.write_png(&options.output) {
+        exit_with_error(error);
+    }
+}
+
+/// Draws a range of turns into an animated GIF or a sequence of PNG files.
+fn export<I: Iterator<Item = String>>(args: I) {
+    let options = RenderOptions::parse(args, Command::Export);
+    let output = options.output.to_lowercase();
+    let gif = if output.ends_with(".gif") {
+        true
+    } else if output.ends_with(".png") {
+        false
+    } else {
+        exit_with_error(format!(
+            "cannot export to {}, the supported formats are .gif and .png",
+            options.output,
+        ));
+    };
+    let mut game_data = options.load_game();
+    let from = options.from.unwrap_or(0);
+    let to = options.to.unwrap_or_else(|| game_data.num_turns().saturating_sub(1));
+    options.check_turn(&game_data, from);
+    options.check_turn(&game_data, to);
+    if from > to {
+        exit_with_error(format!("the range of turns from {} to {} is empty", from, to));
+    }
+    let mut renderer = options.renderer(&game_data);
+    let result = if gif {
+        export_gif(&mut renderer, &game_data, from..=to, options.fps, &options.output)
+    } else {
+        export_png_sequence(&mut renderer, &game_data, from..=to, &options.output)
+    };
+    check_turns_read(&mut game_data);
+    if let Err(error) = result {
+        exit_with_error(error);
+    }
+}
+
+/// Writes the tiles and lines of a single turn into an SVG file.
+fn svg<I: Iterator<Item = String>>(args: I) {
+    let options = RenderOptions::parse(args, Command::Svg);
+    let mut game_data = options.load_game();
+    let turn = options.turn.unwrap_or(0);
+    options.check_turn(&game_data, turn);
+    let mut layers = LayerVisibility::new();
+    for &layer in &options.hidden_layers {
+        layers.hide(layer);
+    }
+    let result = write_svg(&game_data, turn, &layers, &options.output);
+    check_turns_read(&mut game_data);
+    if let Err(error) = result {
+        exit_with_error(error);
+    }
+}
 
 fn main() {
-    let file_name = env::args().nth(1).unwrap();
-    let mut insight = Insight::open(&file_name);
-    insight.run();
-}
\ No newline at end of file
+    match env::args().nth(1).as_deref() {
+        Some("render") => return render(env::args().skip(2)),
+        Some("export") => return export(env::args().skip(2)),
+        Some("svg") => return svg(env::args().skip(2)),
+        _ => (),
+    }
+    let mut file_name = None;
+    let mut follow = false;
+    let mut wrap = false;
+    let mut turn = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--follow" => follow = true,
+            "-w" | "--wrap" => wrap = true,
+            "--turn" => match args.next().and_then(|turn| turn.parse::<u32>().ok()) {
+                Some(start_turn) => turn = Some(start_turn),
+                None => usage(),
+            },
+            _ if file_name.is_none() => file_name = Some(arg),
+            _ => usage(),
+        }
+    }
+    let game_data = match file_name {
+        Some(ref file_name) if file_name != "-" => GameData::load(file_name, follow),
+        None if io::stdin().is_terminal() => usage(),
+        _ => {
+            // a pipe is always followed until it is closed
+            follow = true;
+            GameData::from_reader(BufReader::new(io::stdin()), "<stdin>")
+        },
+    };
+    let mut game_data = match game_data {
+        Ok(game_data) => game_data,
+        Err(error) => exit_with_error(error),
+    };
+    if wrap {
+        game_data.set_wrap(true);
+    }
+    let mut insight = Insight::new(game_data, follow);
+    if let Some(turn) = turn {
+        insight.go_to_turn(turn);
+    }
+    if let Err(error) = insight.run() {
+        exit_with_error(error);
+    }
+}
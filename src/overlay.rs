@@ -0,0 +1,182 @@
+use font;
+use game_data::GameData;
+use layers::LayerVisibility;
+use view::View;
+
+/// Space between the border of a panel and its text, in window pixels.
+pub const PANEL_PADDING: f32 = 8.0;
+pub const PANEL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+pub const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const TITLE_COLOR: [f32; 4] = [1.0, 0.85, 0.4, 1.0];
+/// Labels are hidden when their font pixels would be smaller than this, in window pixels.
+const MIN_LABEL_SCALE: f32 = 1.0;
+const MAX_LABEL_SCALE: f32 = 4.0;
+const LABEL_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const LABEL_SHADOW_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.8];
+
+/// A vertex of the overlay, positioned in pixels from the top left corner of the window.
+#[derive(Copy, Clone)]
+pub struct OverlayVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Text and rectangles to be drawn on top of the board.
+pub struct Overlay {
+    vertices: Vec<OverlayVertex>,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay {
+            vertices: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    /// The triangles of the overlay.
+    pub fn vertices(&self) -> &[OverlayVertex] {
+        &self.vertices
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.glyph(font::SOLID, x, y, width, height, color);
+    }
+
+    /// Draws a line of text with its top left corner at the given position.
+    ///
+    /// The scale is the size of a font pixel in window pixels.
+    pub fn text(&mut self, x: f32, y: f32, scale: f32, color: [f32; 4], text: &str) {
+        let width = font::GLYPH_WIDTH as f32 * scale;
+        let height = font::GLYPH_HEIGHT as f32 * scale;
+        for (i, character) in text.chars().enumerate() {
+            if character == ' ' {
+                continue;
+            }
+            let glyph_x = x + (i as u32 * font::ADVANCE) as f32 * scale;
+            self.glyph(font::glyph_index(character), glyph_x, y, width, height, color);
+        }
+    }
+
+    /// Draws a title and lines of text on a background with its top left corner at the given
+    /// position.
+    pub fn panel(&mut self, x: f32, y: f32, scale: f32, title: &str, lines: &[String]) {
+        let (width, height) = panel_size(scale, title, lines);
+        self.rect(x, y, width, height, PANEL_COLOR);
+        let x = x + PANEL_PADDING;
+        let mut y = y + PANEL_PADDING;
+        self.text(x, y, scale, TITLE_COLOR, title);
+        for line in lines {
+            y += line_height(scale);
+            self.text(x, y, scale, TEXT_COLOR, line);
+        }
+    }
+
+    /// Draws the labels of the visible layers of a turn centered on their cells.
+    pub fn labels(&mut self, game_data: &GameData, turn: u32, layers: &LayerVisibility,
+                  view: &View) {
+        let cell_size = view.cell_size();
+        let num_rows = game_data.num_rows();
+        let num_cols = game_data.num_cols();
+        for label in game_data.labels(turn) {
+            if !layers.is_visible(label.layer) {
+                continue;
+            }
+            // fit the text into the cell, using whole window pixels per font pixel
+            let scale = (0.9 * cell_size / text_width(&label.text, 1.0))
+                .min(0.6 * cell_size / font::GLYPH_HEIGHT as f32)
+                .min(MAX_LABEL_SCALE)
+                .floor();
+            if scale < MIN_LABEL_SCALE {
+                continue;
+            }
+            let (center_x, center_y) = view.cell_center(label.row, label.col, num_rows, num_cols);
+            let left = center_x - text_width(&label.text, scale) / 2.0;
+            let top = center_y - font::GLYPH_HEIGHT as f32 * scale / 2.0;
+            self.text(left + scale, top + scale, scale, LABEL_SHADOW_COLOR, &label.text);
+            self.text(left, top, scale, LABEL_COLOR, &label.text);
+        }
+    }
+
+    fn glyph(&mut self, glyph: usize, x: f32, y: f32, width: f32, height: f32,
+             color: [f32; 4]) {
+        let (top_left, bottom_right) = font::atlas_coords(glyph);
+        let corners = [
+            ([x, y], top_left),
+            ([x + width, y], [bottom_right[0], top_left[1]]),
+            ([x + width, y + height], bottom_right),
+            ([x, y + height], [top_left[0], bottom_right[1]]),
+        ];
+        for &i in &[0, 1, 2, 0, 2, 3] {
+            let (position, tex_coords) = corners[i];
+            self.vertices.push(OverlayVertex {
+                position,
+                tex_coords,
+                color,
+            });
+        }
+    }
+}
+
+/// Width of a line of text in window pixels.
+pub fn text_width(text: &str, scale: f32) -> f32 {
+    chars_width(text.chars().count(), scale)
+}
+
+/// Width of a line of the given number of characters in window pixels.
+pub fn chars_width(num_chars: usize, scale: f32) -> f32 {
+    if num_chars == 0 {
+        return 0.0;
+    }
+    ((num_chars as u32 - 1) * font::ADVANCE + font::GLYPH_WIDTH) as f32 * scale
+}
+
+/// The number of characters fitting into the given width in window pixels.
+pub fn max_chars(width: f32, scale: f32) -> usize {
+    ((width / scale + 1.0) / font::ADVANCE as f32).max(0.0) as usize
+}
+
+/// Height of a line of text including the space to the next line.
+pub fn line_height(scale: f32) -> f32 {
+    (font::GLYPH_HEIGHT + 3) as f32 * scale
+}
+
+/// The size of a panel drawn with the given text.
+pub fn panel_size(scale: f32, title: &str, lines: &[String]) -> (f32, f32) {
+    let text_width = lines.iter()
+        .map(|line| text_width(line, scale))
+        .fold(text_width(title, scale), f32::max);
+    let text_height = (lines.len() + 1) as f32 * line_height(scale);
+    (text_width + 2.0 * PANEL_PADDING, text_height + 2.0 * PANEL_PADDING)
+}
+
+/// Breaks text into lines of at most the given number of characters, preferably at spaces.
+pub fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let line_chars = line.chars().count();
+            let word_chars = word.chars().count();
+            if line_chars != 0 && line_chars + 1 + word_chars > max_chars {
+                lines.push(line.split_off(0));
+            } else if line_chars != 0 {
+                line.push(' ');
+            }
+            let mut chars = word.chars().peekable();
+            while chars.peek().is_some() {
+                if line.chars().count() == max_chars {
+                    lines.push(line.split_off(0));
+                }
+                line.extend(chars.next());
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,327 @@
+use std::io;
+use std::io::BufRead;
+use std::fmt;
+use std::error::Error;
+use std::str::FromStr;
+use std::any::type_name;
+use std::thread;
+use std::time::Duration;
+
+/// How often a followed file is checked for new data.
+const FOLLOW_INTERVAL: u64 = 100;
+
+enum ErrorKind {
+    Io(io::Error),
+    Unexpected {
+        found: Option<String>,
+        expected: String,
+    },
+}
+
+pub struct ParseError {
+    file_name: String,
+    line_number: usize,
+    word_index: Option<usize>,
+    kind: ErrorKind,
+}
+
+impl ParseError {
+    pub fn io(file_name: &str, line_number: usize, error: io::Error) -> ParseError {
+        ParseError {
+            file_name: file_name.to_string(),
+            line_number,
+            word_index: None,
+            kind: ErrorKind::Io(error),
+        }
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// The line the error occurred in, starting at 1, or 0 if the file could not be opened.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// The word within the line the error occurred at, starting at 1.
+    pub fn word_index(&self) -> Option<usize> {
+        self.word_index
+    }
+
+    /// The text that could not be parsed, or None if the line or the file ended too early or
+    /// the file could not be read.
+    pub fn found(&self) -> Option<&str> {
+        match self.kind {
+            ErrorKind::Unexpected { ref found, .. } => found.as_deref(),
+            ErrorKind::Io(_) => None,
+        }
+    }
+
+    /// What the parser was looking for, or None if the file could not be read.
+    pub fn expected(&self) -> Option<&str> {
+        match self.kind {
+            ErrorKind::Unexpected { ref expected, .. } => Some(expected),
+            ErrorKind::Io(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file_name)?;
+        if self.line_number != 0 {
+            write!(f, ":{}", self.line_number)?;
+        }
+        if let Some(word_index) = self.word_index {
+            write!(f, ", word {}", word_index)?;
+        }
+        match self.kind {
+            ErrorKind::Io(ref error) => write!(f, ": could not read file: {}", error),
+            ErrorKind::Unexpected { ref found, ref expected } => match *found {
+                Some(ref found) => write!(f, ": expected {}, found \"{}\"", expected, found),
+                None if self.word_index.is_some() => {
+                    write!(f, ": expected {}, found end of line", expected)
+                },
+                None => write!(f, ": expected {}, found end of file", expected),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Error for ParseError {}
+
+pub struct LineReader<R: BufRead> {
+    reader: R,
+    file_name: String,
+    line_number: usize,
+    position: u64,
+    follow: bool,
+    recording: Option<Vec<u8>>,
+    buffer: String,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R, file_name: &str) -> LineReader<R> {
+        Self::with_position(reader, file_name, 0, 0)
+    }
+
+    /// Creates a reader for a file that has already been read up to the given line and byte
+    /// position.
+    pub fn with_position(reader: R, file_name: &str, line_number: usize, position: u64)
+                         -> LineReader<R> {
+        LineReader {
+            reader,
+            file_name: file_name.to_string(),
+            line_number,
+            position,
+            follow: false,
+            recording: None,
+            buffer: String::new(),
+        }
+    }
+
+    /// Makes the reader keep a copy of every line it reads until the recording is taken.
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = if recording { Some(Vec::new()) } else { None };
+    }
+
+    /// Returns the lines recorded since the last call and starts a new recording.
+    pub fn take_recording(&mut self) -> Option<Vec<u8>> {
+        self.recording.as_mut().map(|recording| recording.split_off(0))
+    }
+
+    /// Makes the reader wait for more data at the end of the file instead of stopping, like
+    /// `tail -f`. Lines are only returned once they are complete, ending with a newline, except
+    /// for a final `end` line.
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow;
+    }
+
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Byte position of the start of the next line.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Reads the next line, returning false at the end of the file.
+    pub fn read_line(&mut self) -> Result<bool, ParseError> {
+        self.buffer.clear();
+        let mut num_bytes = 0;
+        loop {
+            num_bytes += self.reader.read_line(&mut self.buffer)
+                .map_err(|error| ParseError::io(&self.file_name, self.line_number + 1, error))?;
+            // nothing can follow the end of the insight data, so it does not need a newline
+            if !self.follow || self.buffer.ends_with('\n') || self.buffer.trim() == "end" {
+                break;
+            }
+            thread::sleep(Duration::from_millis(FOLLOW_INTERVAL));
+        }
+        if num_bytes == 0 {
+            return Ok(false);
+        }
+        self.line_number += 1;
+        self.position += num_bytes as u64;
+        if let Some(ref mut recording) = self.recording {
+            recording.extend_from_slice(self.buffer.as_bytes());
+        }
+        Ok(true)
+    }
+
+    /// Reads the next line, treating the end of the file as an error.
+    pub fn expect_line(&mut self, expected: &str) -> Result<(), ParseError> {
+        if self.read_line()? {
+            Ok(())
+        } else {
+            Err(ParseError {
+                file_name: self.file_name.clone(),
+                line_number: self.line_number + 1,
+                word_index: None,
+                kind: ErrorKind::Unexpected {
+                    found: None,
+                    expected: expected.to_string(),
+                },
+            })
+        }
+    }
+
+    pub fn words(&self) -> Words<'_> {
+        Words {
+            rest: &self.buffer,
+            file_name: &self.file_name,
+            line_number: self.line_number,
+            word_index: 0,
+        }
+    }
+}
+
+pub struct Words<'a> {
+    rest: &'a str,
+    file_name: &'a str,
+    line_number: usize,
+    word_index: usize,
+}
+
+impl<'a> Words<'a> {
+    /// Returns the next word, or None at the end of the line.
+    pub fn try_next(&mut self) -> Option<&'a str> {
+        let rest = self.rest.trim_start();
+        if rest.is_empty() {
+            self.rest = rest;
+            return None;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.rest = &rest[end..];
+        self.word_index += 1;
+        Some(&rest[..end])
+    }
+
+    /// Whether the next word starts with a double quote.
+    pub fn at_quote(&self) -> bool {
+        self.rest.trim_start().starts_with('"')
+    }
+
+    /// Reads a string in double quotes, in which `\"`, `\\` and `\n` are escaped.
+    pub fn quoted(&mut self, what: &str, context: &str) -> Result<String, ParseError> {
+        let expected = format!("{} in double quotes in {}", what, context);
+        let rest = self.rest.trim_start();
+        if rest.is_empty() {
+            return Err(self.missing(&expected));
+        }
+        self.word_index += 1;
+        let mut chars = rest.char_indices();
+        if chars.next() != Some((0, '"')) {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            self.rest = &rest[end..];
+            return Err(self.error(&rest[..end], &expected));
+        }
+        let mut string = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &rest[i + 1..];
+                    return Ok(string);
+                },
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => string.push('\n'),
+                    Some((_, c)) if c == '"' || c == '\\' => string.push(c),
+                    _ => return Err(self.error(
+                        rest[i..].split_whitespace().next().unwrap_or(""),
+                        &format!("escape sequence in {}", what),
+                    )),
+                },
+                c => string.push(c),
+            }
+        }
+        self.rest = "";
+        Err(self.error(rest.trim_end(), &format!("closing quote of {}", what)))
+    }
+
+    pub fn next_word(&mut self, what: &str, context: &str) -> Result<&'a str, ParseError> {
+        match self.try_next() {
+            Some(word) => Ok(word),
+            None => Err(self.missing(&format!("{} in {}", what, context))),
+        }
+    }
+
+    pub fn parse<T: FromStr>(&mut self, what: &str, context: &str) -> Result<T, ParseError> {
+        let word = self.next_word(what, context)?;
+        word.parse::<T>().map_err(|_| {
+            self.error(word, &format!("{} as {} in {}", what, type_name::<T>(), context))
+        })
+    }
+
+    /// Creates an error pointing at the word that was read last.
+    pub fn error(&self, found: &str, expected: &str) -> ParseError {
+        ParseError {
+            file_name: self.file_name.to_string(),
+            line_number: self.line_number,
+            word_index: Some(self.word_index),
+            kind: ErrorKind::Unexpected {
+                found: Some(found.to_string()),
+                expected: expected.to_string(),
+            },
+        }
+    }
+
+    /// Creates an error for a word missing at the end of the line.
+    pub fn missing(&self, expected: &str) -> ParseError {
+        ParseError {
+            file_name: self.file_name.to_string(),
+            line_number: self.line_number,
+            word_index: Some(self.word_index + 1),
+            kind: ErrorKind::Unexpected {
+                found: None,
+                expected: expected.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::LineReader;
+
+    #[test]
+    fn following_accepts_end_without_newline() {
+        let mut reader = LineReader::new(Cursor::new(b"turn\nend".to_vec()), "test.txt");
+        reader.set_follow(true);
+        assert!(reader.read_line().unwrap());
+        assert_eq!(reader.words().try_next(), Some("turn"));
+        assert!(reader.read_line().unwrap());
+        assert_eq!(reader.words().try_next(), Some("end"));
+        assert_eq!(reader.line_number(), 2);
+        assert_eq!(reader.position(), 8);
+    }
+}
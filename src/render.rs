@@ -0,0 +1,325 @@
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+
+use cgmath::Vector2;
+use cgmath::Vector3;
+use cgmath::Vector4;
+
+use png;
+
+use game_data::GameData;
+use game_data::Shape;
+use layers::LayerVisibility;
+use overlay::Overlay;
+use view::View;
+use torus;
+use font;
+
+/// An area of the image in pixels that drawing is restricted to, as left, top, right and
+/// bottom.
+type Clip = (f32, f32, f32, f32);
+
+/// Draws turns into an image in memory, without a window or OpenGL.
+///
+/// The output matches what `Graphics` draws into a window of the same size.
+pub struct Renderer {
+    view: View,
+    layers: LayerVisibility,
+    label_overlay: Overlay,
+    font_atlas: Vec<u8>,
+    image: Image,
+}
+
+impl Renderer {
+    pub fn new(width: u32, height: u32) -> Renderer {
+        Renderer {
+            view: View::new(width, height),
+            layers: LayerVisibility::new(),
+            label_overlay: Overlay::new(),
+            font_atlas: font::atlas(),
+            image: Image::new(width, height),
+        }
+    }
+
+    pub fn view_mut(&mut self) -> &mut View {
+        &mut self.view
+    }
+
+    pub fn layers_mut(&mut self) -> &mut LayerVisibility {
+        &mut self.layers
+    }
+
+    pub fn width(&self) -> u32 {
+        self.view.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.view.height()
+    }
+
+    /// Draws a turn, or only the background if the turn does not exist.
+    pub fn render(&mut self, game_data: &GameData, turn: u32) {
+        let num_rows = game_data.num_rows();
+        let num_cols = game_data.num_cols();
+        self.view.update(num_rows, num_cols);
+        self.image.clear();
+
+        self.draw_background(game_data);
+        if turn < game_data.num_turns() {
+            let copies = self.view.board_copies(num_rows, num_cols);
+            let clip = if copies.len() > 1 {
+                self.view.board_bounds(num_rows, num_cols)
+            } else {
+                (0.0, 0.0, self.width() as f32, self.height() as f32)
+            };
+            for &copy in &copies {
+                self.draw_heat_maps(game_data, turn, copy, clip);
+            }
+            for &copy in &copies {
+                self.draw_tiles(game_data, turn, copy, clip);
+            }
+            for &copy in &copies {
+                self.draw_lines(game_data, turn, copy, clip);
+            }
+            self.draw_labels(game_data, turn);
+        }
+    }
+
+    /// The image as rows of RGB bytes, starting with the top row.
+    pub fn rgb(&self) -> Vec<u8> {
+        self.image.rgb()
+    }
+
+    /// Writes the image to a PNG file, naming the file in errors.
+    pub fn write_png(&self, file_name: &str) -> io::Result<()> {
+        self.encode_png(file_name).map_err(|error| {
+            io::Error::new(error.kind(), format!("could not write {}: {}", file_name, error))
+        })
+    }
+
+    fn encode_png(&self, file_name: &str) -> io::Result<()> {
+        let file = File::create(file_name)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width(), self.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb())?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Maps a point on a copy of the board to image pixels.
+    fn board_to_screen(&self, x: f32, y: f32, copy: Vector2<f32>) -> (f32, f32) {
+        self.view.board_to_screen(x + copy.x, y + copy.y)
+    }
+
+    fn draw_background(&mut self, game_data: &GameData) {
+        let (left, bottom) = self.view.board_to_screen(0.0, 0.0);
+        let (right, top) = self.view.board_to_screen(
+            game_data.num_cols() as f32,
+            game_data.num_rows() as f32,
+        );
+        let color = game_data.background_color().extend(1.0);
+        let clip = (0.0, 0.0, self.width() as f32, self.height() as f32);
+        self.image.fill(left, top, right, bottom, clip, |_, _| Some(color));
+    }
+
+    fn draw_heat_maps(&mut self, game_data: &GameData, turn: u32, copy: Vector2<f32>,
+                      clip: Clip) {
+        let num_rows = game_data.num_rows();
+        let num_cols = game_data.num_cols();
+        for heat_map in game_data.heat_maps(turn) {
+            if !self.layers.is_visible(heat_map.layer) {
+                continue;
+            }
+            let heat_scale = game_data.heat_scale(heat_map.layer);
+            let bounds = heat_scale.bounds(&heat_map);
+            for (index, &value) in heat_map.values().iter().enumerate() {
+                if value.is_nan() {
+                    continue;
+                }
+                let row = index as u32 / num_cols;
+                let col = index as u32 % num_cols;
+                // heat maps are drawn without blending
+                let color = heat_scale.color(value, bounds).extend(1.0);
+                self.draw_cell(row, col, num_rows, Shape::Square, color, copy, clip);
+            }
+        }
+    }
+
+    fn draw_tiles(&mut self, game_data: &GameData, turn: u32, copy: Vector2<f32>,
+                  clip: Clip) {
+        let num_rows = game_data.num_rows();
+        for tile in game_data.tiles(turn) {
+            if !self.layers.is_visible(tile.layer) {
+                continue;
+            }
+            self.draw_cell(tile.row, tile.col, num_rows, tile.shape, tile.color, copy, clip);
+        }
+    }
+
+    fn draw_lines(&mut self, game_data: &GameData, turn: u32, copy: Vector2<f32>,
+                  clip: Clip) {
+        let num_rows = game_data.num_rows();
+        for line in game_data.lines(turn) {
+            if !self.layers.is_visible(line.layer) {
+                continue;
+            }
+            let segments = torus::line_segments(
+                line.r1,
+                line.c1,
+                line.r2,
+                line.c2,
+                num_rows,
+                game_data.num_cols(),
+                game_data.wraps(),
+            );
+            for [start, end] in segments {
+                let start = self.board_to_screen(start[0], num_rows as f32 - start[1], copy);
+                let end = self.board_to_screen(end[0], num_rows as f32 - end[1], copy);
+                self.image.line(start, end, line.color, clip);
+            }
+        }
+    }
+
+    fn draw_labels(&mut self, game_data: &GameData, turn: u32) {
+        self.label_overlay.clear();
+        self.label_overlay.labels(game_data, turn, &self.layers, &self.view);
+
+        let clip = (0.0, 0.0, self.width() as f32, self.height() as f32);
+        let vertices = self.label_overlay.vertices();
+        // every glyph is a rectangle of two triangles starting at its top left corner
+        for quad in vertices.chunks(6) {
+            let top_left = quad[0];
+            let bottom_right = quad[2];
+            let [left, top] = top_left.position;
+            let [right, bottom] = bottom_right.position;
+            let [u0, v0] = top_left.tex_coords;
+            let [u1, v1] = bottom_right.tex_coords;
+            let color = Vector4::from(top_left.color);
+            let font_atlas = &self.font_atlas;
+            self.image.fill(left, top, right, bottom, clip, |s, t| {
+                let u = u0 + s * (u1 - u0);
+                let v = v0 + t * (v1 - v0);
+                let x = ((u * font::ATLAS_WIDTH as f32) as u32).min(font::ATLAS_WIDTH - 1);
+                let y = ((v * font::ATLAS_HEIGHT as f32) as u32).min(font::ATLAS_HEIGHT - 1);
+                let alpha = font_atlas[((y * font::ATLAS_WIDTH + x) * 4 + 3) as usize];
+                Some(Vector4::new(color.x, color.y, color.z, color.w * alpha as f32 / 255.0))
+            });
+        }
+    }
+
+    /// Draws a shape into a cell like the tile shader.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_cell(&mut self, row: u32, col: u32, num_rows: u32, shape: Shape,
+                 color: Vector4<f32>, copy: Vector2<f32>, clip: Clip) {
+        let x = col as f32;
+        let y = (num_rows - row - 1) as f32;
+        let (left, top) = self.board_to_screen(x, y + 1.0, copy);
+        let (right, bottom) = self.board_to_screen(x + 1.0, y, copy);
+        self.image.fill(left, top, right, bottom, clip, |s, t| {
+            if shape.contains(s - 0.5, 0.5 - t) {
+                Some(color)
+            } else {
+                None
+            }
+        });
+    }
+}
+
+/// Pixels with colors blended like OpenGL does.
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vector3<f32>>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32) -> Image {
+        let num_pixels = (width as usize).checked_mul(height as usize)
+            .unwrap_or_else(|| panic!("An image of {}x{} pixels is too large", width, height));
+        Image {
+            width,
+            height,
+            pixels: vec![Vector3::new(0.0, 0.0, 0.0); num_pixels],
+        }
+    }
+
+    fn clear(&mut self) {
+        for pixel in &mut self.pixels {
+            *pixel = Vector3::new(0.0, 0.0, 0.0);
+        }
+    }
+
+    /// The image as rows of RGB bytes, starting with the top row.
+    fn rgb(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            for &channel in &[pixel.x, pixel.y, pixel.z] {
+                data.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        data
+    }
+
+    /// Blends the colors returned for the pixels whose centers lie in a rectangle, given the
+    /// position of the center within the rectangle from 0 to 1.
+    fn fill<F>(&mut self, left: f32, top: f32, right: f32, bottom: f32, clip: Clip, color: F)
+        where F: Fn(f32, f32) -> Option<Vector4<f32>>
+    {
+        let (first_x, last_x) = pixel_range(left.max(clip.0), right.min(clip.2));
+        let (first_y, last_y) = pixel_range(top.max(clip.1), bottom.min(clip.3));
+        for y in first_y..last_y {
+            let t = (y as f32 + 0.5 - top) / (bottom - top);
+            for x in first_x..last_x {
+                let s = (x as f32 + 0.5 - left) / (right - left);
+                if let Some(color) = color(s, t) {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Draws a line one pixel wide, setting one pixel for every pixel center passed along its
+    /// major axis.
+    fn line(&mut self, start: (f32, f32), end: (f32, f32), color: Vector4<f32>, clip: Clip) {
+        let dx = end.0 - start.0;
+        let dy = end.1 - start.1;
+        let x_major = dx.abs() >= dy.abs();
+        let (from, to) = if x_major {
+            (start.0.min(end.0), start.0.max(end.0))
+        } else {
+            (start.1.min(end.1), start.1.max(end.1))
+        };
+        let (first, last) = pixel_range(from, to);
+        for major in first..last {
+            let center = major as f32 + 0.5;
+            let (x, y) = if x_major {
+                (center, start.1 + (center - start.0) * dy / dx)
+            } else {
+                (start.0 + (center - start.1) * dx / dy, center)
+            };
+            if x >= clip.0 && x < clip.2 && y >= clip.1 && y < clip.3 {
+                self.blend(x as u32, y as u32, color);
+            }
+        }
+    }
+
+    /// Alpha blends a color onto a pixel inside the image.
+    fn blend(&mut self, x: u32, y: u32, color: Vector4<f32>) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = y as usize * self.width as usize + x as usize;
+        let pixel = &mut self.pixels[index];
+        *pixel = color.truncate() * color.w + *pixel * (1.0 - color.w);
+    }
+}
+
+/// The pixels whose centers lie between two coordinates, as a half-open range.
+fn pixel_range(from: f32, to: f32) -> (u32, u32) {
+    let first = (from - 0.5).ceil().max(0.0) as u32;
+    let last = (to - 0.5).ceil().max(0.0) as u32;
+    (first, last.max(first))
+}
@@ -0,0 +1,73 @@
+use std::io::BufRead;
+use std::thread;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+
+use game_data::Header;
+use game_data::Turn;
+use game_data::TurnEnd;
+use game_data::Waypoint;
+use parser::LineReader;
+use parser::ParseError;
+
+/// Where the block of a turn starts within the file.
+pub struct TurnSpan {
+    pub position: u64,
+    pub line_number: usize,
+    /// Whether the turn has messages or labels, to be marked on the timeline.
+    pub annotated: bool,
+}
+
+/// A turn found by the scanner.
+///
+/// The text of the turn is only included if the reader is recording, which is the case for
+/// inputs that cannot be read again later, like pipes.
+pub struct ScannedTurn {
+    pub span: TurnSpan,
+    pub text: Option<Vec<u8>>,
+    pub turn: Turn,
+    /// The positions of the entities in the turn, by id.
+    pub waypoints: Vec<(u32, Waypoint)>,
+}
+
+pub type Scanner = Receiver<Result<ScannedTurn, ParseError>>;
+
+/// Indexes the turns of a file in a background thread.
+///
+/// The reader has to be positioned right after the header. Every turn is fully parsed to
+/// report errors early, and sent back along with its span and the positions of its entities.
+pub fn spawn<R>(header: Header, mut reader: LineReader<R>) -> Scanner
+    where R: BufRead + Send + 'static
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for turn_number in 0.. {
+            let position = reader.position();
+            let line_number = reader.line_number();
+            let (turn, end) = match Turn::parse(&header, &mut reader) {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    let _ = sender.send(Err(error));
+                    return;
+                },
+            };
+            let span = TurnSpan {
+                position,
+                line_number,
+                annotated: turn.is_annotated(),
+            };
+            let text = reader.take_recording();
+            let waypoints = turn.waypoints(&header, turn_number);
+            let scanned_turn = ScannedTurn {
+                span,
+                text,
+                turn,
+                waypoints,
+            };
+            if sender.send(Ok(scanned_turn)).is_err() || end != TurnEnd::NextTurn {
+                return;
+            }
+        }
+    });
+    receiver
+}
@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::io;
+use std::io::Write as IoWrite;
+
+use cgmath::Vector3;
+use cgmath::Vector4;
+
+use game_data::GameData;
+use game_data::Shape;
+use game_data::Tile;
+use layers::LayerVisibility;
+use torus;
+
+/// The outline of an arrow pointing north, relative to the center of its cell with y pointing
+/// down, matching the arrows of the tile shader.
+const ARROW: [(f32, f32); 7] = [
+    (0.0, -0.5),
+    (0.4, 0.0),
+    (0.12, 0.0),
+    (0.12, 0.5),
+    (-0.12, 0.5),
+    (-0.12, 0.0),
+    (-0.4, 0.0),
+];
+const TRIANGLE: [(f32, f32); 3] = [(0.0, -0.5), (0.5, 0.5), (-0.5, 0.5)];
+const DIAMOND: [(f32, f32); 4] = [(0.0, -0.5), (0.5, 0.0), (0.0, 0.5), (-0.5, 0.0)];
+/// One of the two bars of a cross, clipped to its cell.
+const CROSS_BAR: [(f32, f32); 6] = [
+    (-0.5, -0.5),
+    (-0.35, -0.5),
+    (0.5, 0.35),
+    (0.5, 0.5),
+    (0.35, 0.5),
+    (-0.5, -0.35),
+];
+
+/// Writes the tiles and lines of a turn as an SVG image.
+///
+/// The image uses board coordinates, a cell being a unit square with the top left corner of
+/// the board at the origin. Every layer is a group with the id layer-N, hidden layers are
+/// included but not displayed.
+pub fn svg(game_data: &GameData, turn: u32, layers: &LayerVisibility) -> String {
+    let num_rows = game_data.num_rows();
+    let num_cols = game_data.num_cols();
+    let mut layer_elements: BTreeMap<u32, String> = BTreeMap::new();
+    for tile in game_data.tiles(turn) {
+        let elements = layer_elements.entry(tile.layer).or_default();
+        write_tile(elements, &tile);
+    }
+    for line in game_data.lines(turn) {
+        let elements = layer_elements.entry(line.layer).or_default();
+        let segments = torus::line_segments(
+            line.r1,
+            line.c1,
+            line.r2,
+            line.c2,
+            num_rows,
+            num_cols,
+            game_data.wraps(),
+        );
+        for [start, end] in segments {
+            // lines are as thin as in the viewer, whatever the zoom
+            let _ = writeln!(
+                elements,
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {} stroke-width=\"1\" \
+                 vector-effect=\"non-scaling-stroke\"/>",
+                start[0],
+                start[1],
+                end[0],
+                end[1],
+                paint("stroke", line.color),
+            );
+        }
+    }
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" \
+         width=\"{}\" height=\"{}\">",
+        num_cols,
+        num_rows,
+        num_cols * 16,
+        num_rows * 16,
+    );
+    let _ = writeln!(
+        svg,
+        "<rect id=\"background\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        num_cols,
+        num_rows,
+        color(game_data.background_color()),
+    );
+    for (layer, elements) in layer_elements {
+        let display = if layers.is_visible(layer) { "" } else { " display=\"none\"" };
+        let _ = writeln!(svg, "<g id=\"layer-{}\"{}>", layer, display);
+        svg.push_str(&elements);
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn write_svg(game_data: &GameData, turn: u32, layers: &LayerVisibility, file_name: &str)
+                 -> io::Result<()> {
+    File::create(file_name)
+        .and_then(|mut file| file.write_all(svg(game_data, turn, layers).as_bytes()))
+        .map_err(|error| {
+            io::Error::new(error.kind(), format!("could not write {}: {}", file_name, error))
+        })
+}
+
+fn write_tile(elements: &mut String, tile: &Tile) {
+    let x = tile.col as f32;
+    let y = tile.row as f32;
+    let center_x = x + 0.5;
+    let center_y = y + 0.5;
+    let fill = paint("fill", tile.color);
+    let _ = match tile.shape {
+        Shape::Square => {
+            writeln!(elements, "<rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\" {}/>", x, y,
+                     fill)
+        },
+        Shape::Circle => {
+            writeln!(elements, "<circle cx=\"{}\" cy=\"{}\" r=\"0.5\" {}/>", center_x, center_y,
+                     fill)
+        },
+        Shape::Ring => {
+            let stroke = paint("stroke", tile.color);
+            writeln!(
+                elements,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"0.4\" fill=\"none\" stroke-width=\"0.2\" {}/>",
+                center_x,
+                center_y,
+                stroke,
+            )
+        },
+        Shape::Triangle => write_polygon(elements, center_x, center_y, &TRIANGLE, 0, &fill),
+        Shape::Diamond => write_polygon(elements, center_x, center_y, &DIAMOND, 0, &fill),
+        Shape::Cross => {
+            let _ = write_polygon(elements, center_x, center_y, &CROSS_BAR, 0, &fill);
+            write_polygon(elements, center_x, center_y, &CROSS_BAR, 90, &fill)
+        },
+        Shape::ArrowNorth => write_polygon(elements, center_x, center_y, &ARROW, 0, &fill),
+        Shape::ArrowEast => write_polygon(elements, center_x, center_y, &ARROW, 90, &fill),
+        Shape::ArrowSouth => write_polygon(elements, center_x, center_y, &ARROW, 180, &fill),
+        Shape::ArrowWest => write_polygon(elements, center_x, center_y, &ARROW, 270, &fill),
+    };
+}
+
+/// Writes a polygon given relative to the center of a cell, rotated clockwise by a multiple of
+/// 90 degrees.
+fn write_polygon(elements: &mut String, center_x: f32, center_y: f32, points: &[(f32, f32)],
+                 rotation: u32, fill: &str) -> ::std::fmt::Result {
+    elements.push_str("<polygon points=\"");
+    for (i, &(x, y)) in points.iter().enumerate() {
+        let (x, y) = match rotation {
+            90 => (-y, x),
+            180 => (-x, -y),
+            270 => (y, -x),
+            _ => (x, y),
+        };
+        let separator = if i == 0 { "" } else { " " };
+        write!(elements, "{}{},{}", separator, round(center_x + x), round(center_y + y))?;
+    }
+    writeln!(elements, "\" {}/>", fill)
+}
+
+/// Rounds away the noise of adding offsets to coordinates.
+fn round(value: f32) -> f32 {
+    (value * 1000.0).round() / 1000.0
+}
+
+fn color(color: Vector3<f32>) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(color.x), channel(color.y), channel(color.z))
+}
+
+/// The attributes to fill or stroke with a color, leaving out the opacity of opaque colors.
+fn paint(attribute: &str, color: Vector4<f32>) -> String {
+    let rgb = self::color(color.truncate());
+    if color.w >= 1.0 {
+        format!("{}=\"{}\"", attribute, rgb)
+    } else {
+        format!("{}=\"{}\" {}-opacity=\"{}\"", attribute, rgb, attribute, color.w.max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector4;
+
+    use game_data::Shape;
+    use layers::LayerVisibility;
+    use writer::tests::written;
+    use super::svg;
+
+    #[test]
+    fn layers_are_groups() {
+        let red = Vector4::new(1.0, 0.0, 0.0, 1.0);
+        let game_data = written(2, 3, |writer| {
+            writer.add_palette_entry("a", Shape::Circle, red, 1);
+            writer.add_palette_entry("b", Shape::Square, red, 2);
+            writer.tile(0, 0, "a");
+            writer.tile(1, 2, "b");
+            writer.line(0, 0, 1, 2, red, 3);
+            writer.end_turn().unwrap();
+        });
+        let mut layers = LayerVisibility::new();
+        layers.hide(2);
+        let svg = svg(&game_data, 0, &layers);
+
+        let groups: Vec<&str> = svg.lines().filter(|line| line.starts_with("<g ")).collect();
+        assert_eq!(groups, vec![
+            "<g id=\"layer-1\">",
+            "<g id=\"layer-2\" display=\"none\">",
+            "<g id=\"layer-3\">",
+        ]);
+        // every element is inside the group of its layer
+        let layer_1 = svg.split("<g id=\"layer-1\">").nth(1).unwrap().split("</g>").next().unwrap();
+        assert!(layer_1.contains("<circle cx=\"0.5\" cy=\"0.5\" r=\"0.5\" fill=\"#ff0000\"/>"));
+        let layer_2 = svg.split("display=\"none\">").nth(1).unwrap().split("</g>").next().unwrap();
+        assert!(layer_2.contains("<rect x=\"2\" y=\"1\" width=\"1\" height=\"1\""));
+        let layer_3 = svg.split("<g id=\"layer-3\">").nth(1).unwrap().split("</g>").next().unwrap();
+        assert!(layer_3.contains("<line x1=\"0.5\" y1=\"0.5\" x2=\"2.5\" y2=\"1.5\""));
+        assert!(svg.ends_with("</g>\n</svg>\n"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Geometry of boards whose edges wrap around, like the maps of Ants.
+
+/// A straight piece of a line in board coordinates, where cells are unit squares, x runs along
+/// the columns and y down along the rows.
+pub type Segment = [[f32; 2]; 2];
+
+/// The pieces to draw for a line between the centers of two cells.
+///
+/// On a wrapping board the line takes the shortest way around the torus and is cut into
+/// several segments where it crosses the edges of the board.
+pub fn line_segments(r1: u32, c1: u32, r2: u32, c2: u32, num_rows: u32, num_cols: u32,
+                     wrap: bool) -> Vec<Segment> {
+    let start = [c1 as f32 + 0.5, r1 as f32 + 0.5];
+    if !wrap || (r1 == r2 && c1 == c2) {
+        return vec![[start, [c2 as f32 + 0.5, r2 as f32 + 0.5]]];
+    }
+    let dx = shortest_offset(c1, c2, num_cols) as f32;
+    let dy = shortest_offset(r1, r2, num_rows) as f32;
+    let width = num_cols as f32;
+    let height = num_rows as f32;
+    let mut segments = Vec::new();
+    // the shortest way crosses each edge at most once, so the copies of the line shifted by
+    // one board size in each direction cover all of its pieces
+    for &shift_x in &[-width, 0.0, width] {
+        for &shift_y in &[-height, 0.0, height] {
+            let shifted_start = [start[0] + shift_x, start[1] + shift_y];
+            let shifted_end = [shifted_start[0] + dx, shifted_start[1] + dy];
+            if let Some(segment) = clip(shifted_start, shifted_end, width, height) {
+                segments.push(segment);
+            }
+        }
+    }
+    segments
+}
+
+/// The offset from one coordinate to another with the smallest magnitude on a ring of the
+/// given size.
+pub fn shortest_offset(from: u32, to: u32, size: u32) -> i64 {
+    let size = i64::from(size);
+    if size == 0 {
+        return 0;
+    }
+    let offset = (i64::from(to) - i64::from(from)).rem_euclid(size);
+    if 2 * offset > size { offset - size } else { offset }
+}
+
+/// Cuts a segment of non-zero length to the board, dropping it if nothing of it is left.
+fn clip(start: [f32; 2], end: [f32; 2], width: f32, height: f32) -> Option<Segment> {
+    let dx = end[0] - start[0];
+    let dy = end[1] - start[1];
+    let mut t_start = 0.0f32;
+    let mut t_end = 1.0f32;
+    let bounds = [
+        (-dx, start[0]),
+        (dx, width - start[0]),
+        (-dy, start[1]),
+        (dy, height - start[1]),
+    ];
+    for &(direction, distance) in &bounds {
+        if direction == 0.0 {
+            if distance < 0.0 {
+                return None;
+            }
+        } else if direction < 0.0 {
+            t_start = t_start.max(distance / direction);
+        } else {
+            t_end = t_end.min(distance / direction);
+        }
+    }
+    if t_start >= t_end {
+        return None;
+    }
+    Some([
+        [start[0] + t_start * dx, start[1] + t_start * dy],
+        [start[0] + t_end * dx, start[1] + t_end * dy],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clip;
+    use super::line_segments;
+    use super::shortest_offset;
+
+    #[test]
+    fn shortest_offset_wraps_around() {
+        assert_eq!(shortest_offset(1, 4, 6), 3);
+        assert_eq!(shortest_offset(4, 1, 6), 3);
+        assert_eq!(shortest_offset(0, 5, 6), -1);
+        assert_eq!(shortest_offset(5, 0, 6), 1);
+        assert_eq!(shortest_offset(2, 2, 6), 0);
+        assert_eq!(shortest_offset(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn line_without_wrapping() {
+        assert_eq!(line_segments(0, 0, 3, 5, 4, 6, false), vec![[[0.5, 0.5], [5.5, 3.5]]]);
+    }
+
+    #[test]
+    fn line_within_the_board() {
+        assert_eq!(line_segments(1, 1, 2, 3, 4, 6, true), vec![[[1.5, 1.5], [3.5, 2.5]]]);
+    }
+
+    #[test]
+    fn line_in_one_cell() {
+        assert_eq!(line_segments(1, 1, 1, 1, 4, 6, true), vec![[[1.5, 1.5], [1.5, 1.5]]]);
+    }
+
+    #[test]
+    fn line_across_the_right_edge() {
+        assert_eq!(line_segments(1, 5, 1, 0, 4, 6, true), vec![
+            [[0.0, 1.5], [0.5, 1.5]],
+            [[5.5, 1.5], [6.0, 1.5]],
+        ]);
+    }
+
+    #[test]
+    fn line_across_the_left_edge() {
+        assert_eq!(line_segments(1, 0, 1, 5, 4, 6, true), vec![
+            [[0.5, 1.5], [0.0, 1.5]],
+            [[6.0, 1.5], [5.5, 1.5]],
+        ]);
+    }
+
+    #[test]
+    fn line_across_the_top_edge() {
+        assert_eq!(line_segments(0, 2, 3, 2, 4, 6, true), vec![
+            [[2.5, 0.5], [2.5, 0.0]],
+            [[2.5, 4.0], [2.5, 3.5]],
+        ]);
+    }
+
+    #[test]
+    fn line_across_the_bottom_edge() {
+        assert_eq!(line_segments(3, 2, 0, 2, 4, 6, true), vec![
+            [[2.5, 0.0], [2.5, 0.5]],
+            [[2.5, 3.5], [2.5, 4.0]],
+        ]);
+    }
+
+    #[test]
+    fn line_across_a_corner() {
+        // two columns to the left and one row up from the top left cell
+        assert_eq!(line_segments(0, 0, 3, 4, 4, 6, true), vec![
+            [[0.5, 0.5], [0.0, 0.25]],
+            [[6.0, 0.25], [5.5, 0.0]],
+            [[5.5, 4.0], [4.5, 3.5]],
+        ]);
+    }
+
+    #[test]
+    fn clip_to_the_board() {
+        assert_eq!(clip([1.0, 1.0], [3.0, 2.0], 6.0, 4.0), Some([[1.0, 1.0], [3.0, 2.0]]));
+        assert_eq!(clip([-1.0, 1.0], [7.0, 1.0], 6.0, 4.0), Some([[0.0, 1.0], [6.0, 1.0]]));
+        assert_eq!(clip([5.0, 3.0], [7.0, 5.0], 6.0, 4.0), Some([[5.0, 3.0], [6.0, 4.0]]));
+        assert_eq!(clip([-2.0, 1.0], [-1.0, 3.0], 6.0, 4.0), None);
+        assert_eq!(clip([1.0, 5.0], [3.0, 5.0], 6.0, 4.0), None);
+        // touching a corner leaves nothing to draw
+        assert_eq!(clip([-1.0, 1.0], [1.0, -1.0], 6.0, 4.0), None);
+    }
+}
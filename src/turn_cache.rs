@@ -0,0 +1,74 @@
+use std::rc::Rc;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+/// Keeps the most recently used turns.
+pub struct TurnCache<T> {
+    capacity: usize,
+    entries: HashMap<u32, Rc<T>>,
+    usage: VecDeque<u32>,
+}
+
+impl<T> TurnCache<T> {
+    pub fn new(capacity: usize) -> TurnCache<T> {
+        TurnCache {
+            capacity,
+            entries: HashMap::new(),
+            usage: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, turn: u32) -> Option<Rc<T>> {
+        let entry = self.entries.get(&turn)?.clone();
+        self.touch(turn);
+        Some(entry)
+    }
+
+    pub fn insert(&mut self, turn: u32, entry: Rc<T>) {
+        if self.entries.insert(turn, entry).is_some() {
+            self.touch(turn);
+            return;
+        }
+        self.usage.push_back(turn);
+        if self.usage.len() > self.capacity {
+            if let Some(oldest) = self.usage.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn touch(&mut self, turn: u32) {
+        if let Some(index) = self.usage.iter().position(|&t| t == turn) {
+            self.usage.remove(index);
+        }
+        self.usage.push_back(turn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::TurnCache;
+
+    #[test]
+    fn evicts_the_least_recently_used_turn() {
+        let mut cache = TurnCache::new(2);
+        cache.insert(1, Rc::new("one"));
+        cache.insert(2, Rc::new("two"));
+        // reading turn 1 makes turn 2 the oldest
+        assert_eq!(cache.get(1).as_deref(), Some(&"one"));
+        cache.insert(3, Rc::new("three"));
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1).as_deref(), Some(&"one"));
+        assert_eq!(cache.get(3).as_deref(), Some(&"three"));
+
+        // inserting a cached turn again replaces it without evicting anything
+        cache.insert(1, Rc::new("uno"));
+        assert_eq!(cache.get(3).as_deref(), Some(&"three"));
+        assert_eq!(cache.get(1).as_deref(), Some(&"uno"));
+        cache.insert(4, Rc::new("four"));
+        assert!(cache.get(3).is_none());
+        assert_eq!(cache.get(1).as_deref(), Some(&"uno"));
+    }
+}
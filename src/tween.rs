@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use cgmath::Vector4;
+
+use game_data::GameData;
+use game_data::Shape;
+use game_data::Tile;
+use torus;
+
+/// Offsets to the cells reachable in one step, as rows and columns.
+const STEPS: [(i64, i64); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+/// A tile on its way from one turn to the next, at a position between cells.
+///
+/// The position may reach up to a cell beyond the edges of the board. On a wrapping board a
+/// tile overlapping an edge comes with copies shifted by the board size, so that the parts
+/// clipped away at one edge show up at the opposite one.
+pub struct TweenTile {
+    pub row: f32,
+    pub col: f32,
+    pub layer: u32,
+    pub shape: Shape,
+    pub color: Vector4<f32>,
+}
+
+/// The tiles in between a turn and the next one, the progress going from 0 at the turn to 1
+/// at the next turn.
+///
+/// An entity slides over to where the entity with the same id is in the next turn. Any other
+/// tile is matched with a tile of the same symbol in the next turn that is in the same cell,
+/// or else one step away. Tiles without a match fade out, new tiles fade in.
+pub fn tween_tiles(game_data: &GameData, turn: u32, progress: f32) -> Vec<TweenTile> {
+    let num_rows = game_data.num_rows();
+    let num_cols = game_data.num_cols();
+    let from = game_data.tiles_with_symbols(turn);
+    let to = if turn + 1 < game_data.num_turns() {
+        game_data.tiles_with_symbols(turn + 1)
+    } else {
+        Vec::new()
+    };
+
+    // the unmatched tiles of the next turn by entity id, or by symbol and cell
+    let mut entity_targets: HashMap<u32, usize> = HashMap::new();
+    let mut targets: HashMap<(&str, u32, u32), Vec<usize>> = HashMap::new();
+    for (index, &(symbol, ref tile)) in to.iter().enumerate().rev() {
+        match tile.entity {
+            Some(id) => {
+                entity_targets.insert(id, index);
+            },
+            None => targets.entry((symbol, tile.row, tile.col)).or_default().push(index),
+        }
+    }
+    let mut matches: Vec<Option<usize>> = from.iter()
+        .map(|&(symbol, ref tile)| match tile.entity {
+            Some(id) => entity_targets.get(&id).cloned(),
+            None => take_target(&mut targets, symbol, tile.row, tile.col),
+        })
+        .collect();
+    for (index, &(symbol, ref tile)) in from.iter().enumerate() {
+        if matches[index].is_some() || tile.entity.is_some() {
+            continue;
+        }
+        for &(row_step, col_step) in &STEPS {
+            let row = i64::from(tile.row) + row_step;
+            let col = i64::from(tile.col) + col_step;
+            let cell = if game_data.wraps() {
+                Some((row.rem_euclid(i64::from(num_rows)), col.rem_euclid(i64::from(num_cols))))
+            } else if row >= 0 && col >= 0 && row < i64::from(num_rows)
+                && col < i64::from(num_cols) {
+                Some((row, col))
+            } else {
+                None
+            };
+            if let Some((row, col)) = cell {
+                matches[index] = take_target(&mut targets, symbol, row as u32, col as u32);
+                if matches[index].is_some() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut matched = vec![false; to.len()];
+    let mut tiles = Vec::new();
+    for ((_, tile), &target) in from.iter().zip(&matches) {
+        match target {
+            Some(target) => {
+                matched[target] = true;
+                let next = &to[target];
+                let row_offset = offset(tile.row, next.1.row, num_rows, game_data.wraps());
+                let col_offset = offset(tile.col, next.1.col, num_cols, game_data.wraps());
+                let row = tile.row as f32 + row_offset * progress;
+                let col = tile.col as f32 + col_offset * progress;
+                let color = tile.color + (next.1.color - tile.color) * progress;
+                for &row in &edge_copies(row, num_rows, game_data.wraps()) {
+                    for &col in &edge_copies(col, num_cols, game_data.wraps()) {
+                        tiles.push(TweenTile {
+                            row,
+                            col,
+                            layer: tile.layer,
+                            shape: tile.shape,
+                            color,
+                        });
+                    }
+                }
+            },
+            None => tiles.push(fading(tile, 1.0 - progress)),
+        }
+    }
+    for ((_, tile), &is_matched) in to.iter().zip(&matched) {
+        if !is_matched {
+            tiles.push(fading(tile, progress));
+        }
+    }
+    tiles
+}
+
+/// Takes the first unmatched tile of the next turn with the symbol in the cell.
+fn take_target<'a>(targets: &mut HashMap<(&'a str, u32, u32), Vec<usize>>, symbol: &'a str,
+                   row: u32, col: u32) -> Option<usize> {
+    targets.get_mut(&(symbol, row, col)).and_then(|indices| indices.pop())
+}
+
+/// The distance from one coordinate to another, going around the edge if that is shorter on
+/// a wrapping board.
+fn offset(from: u32, to: u32, size: u32, wrap: bool) -> f32 {
+    if wrap {
+        torus::shortest_offset(from, to, size) as f32
+    } else {
+        to as f32 - from as f32
+    }
+}
+
+/// The coordinate of a tile of size 1, plus its copy on the other side of a wrapping board if
+/// the tile sticks out over an edge.
+fn edge_copies(position: f32, size: u32, wrap: bool) -> Vec<f32> {
+    let size = size as f32;
+    if wrap && position < 0.0 {
+        vec![position, position + size]
+    } else if wrap && position > size - 1.0 {
+        vec![position, position - size]
+    } else {
+        vec![position]
+    }
+}
+
+fn fading(tile: &Tile, opacity: f32) -> TweenTile {
+    let mut color = tile.color;
+    color.w *= opacity;
+    TweenTile {
+        row: tile.row as f32,
+        col: tile.col as f32,
+        layer: tile.layer,
+        shape: tile.shape,
+        color,
+    }
+}
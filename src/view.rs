@@ -0,0 +1,242 @@
+use cgmath::Matrix4;
+use cgmath::Vector2;
+use cgmath::Vector4;
+use cgmath::SquareMatrix;
+
+use camera::Camera;
+
+/// Where the board appears on the screen: fitted into the screen, zoomed and panned by the
+/// camera and scrolled around its wrapping edges.
+///
+/// Board coordinates have the lower left corner of the board at the origin and cells of size 1,
+/// screen coordinates are pixels from the top left corner of the screen.
+pub struct View {
+    width: u32,
+    height: u32,
+    camera: Camera,
+    scroll: Vector2<f32>,
+    transformation_matrix: Matrix4<f32>,
+}
+
+impl View {
+    pub fn new(width: u32, height: u32) -> View {
+        View {
+            width,
+            height,
+            camera: Camera::new(),
+            scroll: Vector2::new(0.0, 0.0),
+            transformation_matrix: Matrix4::identity(),
+        }
+    }
+
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The center of the screen in pixels.
+    pub fn center(&self) -> (f32, f32) {
+        (self.width as f32 / 2.0, self.height as f32 / 2.0)
+    }
+
+    /// Recomputes the transformation for a board of the given size.
+    pub fn update(&mut self, num_rows: u32, num_cols: u32) {
+        let board_width = num_cols as f32;
+        let board_height = num_rows as f32;
+        let board_ratio = if num_rows != 0 {
+            board_width / board_height
+        } else {
+            0.0
+        };
+        let screen_ratio = if self.height != 0 {
+            self.width as f32 / self.height as f32
+        } else {
+            0.0
+        };
+        let x_offset;
+        let y_offset;
+        let x_scaling;
+        let y_scaling;
+        if screen_ratio > board_ratio {
+            x_scaling = 2.0 / board_height / screen_ratio;
+            y_scaling = 2.0 / board_height;
+            x_offset = -board_ratio / screen_ratio;
+            y_offset = -1.0;
+        } else {
+            x_scaling = 2.0 / board_width;
+            y_scaling = 2.0 / board_width * screen_ratio;
+            x_offset = -1.0;
+            y_offset = -screen_ratio / board_ratio;
+        }
+        let fit_matrix = Matrix4::new(
+            x_scaling,  0.0,       0.0, 0.0,
+            0.0,        y_scaling, 0.0, 0.0,
+            0.0,        0.0,       1.0, 0.0,
+            x_offset,   y_offset,  0.0, 1.0f32,
+        );
+        self.transformation_matrix = self.camera.matrix() * fit_matrix;
+    }
+
+    /// Maps board coordinates to normalized device coordinates, without scrolling.
+    pub fn transformation_matrix(&self) -> Matrix4<f32> {
+        self.transformation_matrix
+    }
+
+    /// How far the board is scrolled, in cells along the board coordinates.
+    pub fn scroll_offset(&self) -> Vector2<f32> {
+        self.scroll
+    }
+
+    /// Scrolls the view over the board by the given number of cells to the right and down,
+    /// wrapping around at the edges.
+    pub fn scroll(&mut self, right: f32, down: f32, num_rows: u32, num_cols: u32) {
+        if num_rows == 0 || num_cols == 0 {
+            return;
+        }
+        self.scroll.x = (self.scroll.x + right).rem_euclid(num_cols as f32);
+        self.scroll.y = (self.scroll.y - down).rem_euclid(num_rows as f32);
+    }
+
+    /// Scrolls like `scroll`, but by a distance in screen pixels.
+    pub fn scroll_pixels(&mut self, right: f32, down: f32, num_rows: u32, num_cols: u32) {
+        let cell_size = self.cell_size();
+        if cell_size > 0.0 {
+            self.scroll(right / cell_size, down / cell_size, num_rows, num_cols);
+        }
+    }
+
+    /// Zooms in by a factor, or out for factors below 1, keeping the given screen position in
+    /// place.
+    pub fn zoom(&mut self, factor: f32, x: f32, y: f32) {
+        let point = self.screen_to_normalized(x, y);
+        self.camera.zoom_at(factor, point);
+    }
+
+    /// Moves the board by a distance in screen pixels.
+    pub fn pan(&mut self, right: f32, down: f32) {
+        if self.width != 0 && self.height != 0 {
+            let right = 2.0 * right / self.width as f32;
+            let down = 2.0 * down / self.height as f32;
+            self.camera.pan(Vector2::new(right, -down));
+        }
+    }
+
+    /// Moves the board so that the center of a cell is in the center of the screen.
+    pub fn center_on(&mut self, row: u32, col: u32, num_rows: u32, num_cols: u32) {
+        self.update(num_rows, num_cols);
+        let (x, y) = self.cell_center(row, col, num_rows, num_cols);
+        let (center_x, center_y) = self.center();
+        self.pan(center_x - x, center_y - y);
+    }
+
+    /// Centers a cell like `center_on`. A wrapping board is also scrolled to put the cell into
+    /// its middle, so that the surroundings of the cell are visible on every side.
+    pub fn follow(&mut self, row: u32, col: u32, num_rows: u32, num_cols: u32, wrap: bool) {
+        if wrap && num_rows != 0 && num_cols != 0 {
+            let x = col as f32 + 0.5;
+            let y = (num_rows - row - 1) as f32 + 0.5;
+            self.scroll.x = (x - num_cols as f32 / 2.0).rem_euclid(num_cols as f32);
+            self.scroll.y = (y - num_rows as f32 / 2.0).rem_euclid(num_rows as f32);
+        }
+        self.center_on(row, col, num_rows, num_cols);
+        self.update(num_rows, num_cols);
+    }
+
+    /// Fits the whole unscrolled board into the screen again.
+    pub fn reset(&mut self) {
+        self.camera.reset();
+        self.scroll = Vector2::new(0.0, 0.0);
+    }
+
+    /// The size of a cell in screen pixels.
+    pub fn cell_size(&self) -> f32 {
+        self.transformation_matrix.x.x * self.width as f32 / 2.0
+    }
+
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll.x != 0.0 || self.scroll.y != 0.0
+    }
+
+    /// The translations of the copies of the board needed to show it scrolled, each shifted by
+    /// a multiple of the board size. They have to be clipped to `board_bounds`.
+    pub fn board_copies(&self, num_rows: u32, num_cols: u32) -> Vec<Vector2<f32>> {
+        let shifts_x: &[f32] = if self.scroll.x != 0.0 { &[0.0, 1.0] } else { &[0.0] };
+        let shifts_y: &[f32] = if self.scroll.y != 0.0 { &[0.0, 1.0] } else { &[0.0] };
+        let mut copies = Vec::new();
+        for &shift_x in shifts_x {
+            for &shift_y in shifts_y {
+                copies.push(Vector2::new(
+                    shift_x * num_cols as f32 - self.scroll.x,
+                    shift_y * num_rows as f32 - self.scroll.y,
+                ));
+            }
+        }
+        copies
+    }
+
+    /// The area of the unscrolled board on the screen as left, top, right and bottom in
+    /// pixels, clipped to the screen.
+    pub fn board_bounds(&self, num_rows: u32, num_cols: u32) -> (f32, f32, f32, f32) {
+        self.board_area(0.0, 0.0, num_cols as f32, num_rows as f32)
+    }
+
+    /// Like `board_bounds`, but for a rectangle of the unscrolled board given by its lower left
+    /// and upper right corners in board coordinates.
+    pub fn board_area(&self, x1: f32, y1: f32, x2: f32, y2: f32) -> (f32, f32, f32, f32) {
+        let (left, bottom) = self.board_to_screen(x1, y1);
+        let (right, top) = self.board_to_screen(x2, y2);
+        let width = self.width as f32;
+        let height = self.height as f32;
+        (
+            left.clamp(0.0, width),
+            top.clamp(0.0, height),
+            right.clamp(0.0, width),
+            bottom.clamp(0.0, height),
+        )
+    }
+
+    /// Maps a point on the unscrolled board to screen pixels.
+    pub fn board_to_screen(&self, x: f32, y: f32) -> (f32, f32) {
+        let normalized = self.transformation_matrix * Vector4::new(x, y, 0.0, 1.0);
+        (
+            (normalized.x + 1.0) / 2.0 * self.width as f32,
+            (1.0 - normalized.y) / 2.0 * self.height as f32,
+        )
+    }
+
+    /// Maps the center of a cell to screen pixels, taking scrolling into account.
+    pub fn cell_center(&self, row: u32, col: u32, num_rows: u32, num_cols: u32) -> (f32, f32) {
+        let x = (col as f32 + 0.5 - self.scroll.x).rem_euclid(num_cols as f32);
+        let y = ((num_rows - row - 1) as f32 + 0.5 - self.scroll.y).rem_euclid(num_rows as f32);
+        self.board_to_screen(x, y)
+    }
+
+    /// Maps a position on the screen to the cell under it.
+    pub fn screen_to_cell(&self, x: f32, y: f32, num_rows: u32, num_cols: u32)
+                          -> Option<(u32, u32)> {
+        let inverse = self.transformation_matrix.invert()?;
+        let normalized = self.screen_to_normalized(x, y);
+        let board = inverse * Vector4::new(normalized.x, normalized.y, 0.0, 1.0);
+        let board_width = num_cols as f32;
+        let board_height = num_rows as f32;
+        if board.x < 0.0 || board.y < 0.0 || board.x >= board_width || board.y >= board_height {
+            return None;
+        }
+        let col = ((board.x + self.scroll.x).rem_euclid(board_width) as u32).min(num_cols - 1);
+        let flipped_row = ((board.y + self.scroll.y).rem_euclid(board_height) as u32)
+            .min(num_rows - 1);
+        Some((num_rows - flipped_row - 1, col))
+    }
+
+    fn screen_to_normalized(&self, x: f32, y: f32) -> Vector2<f32> {
+        Vector2::new(x / self.width as f32 * 2.0 - 1.0, 1.0 - y / self.height as f32 * 2.0)
+    }
+}
@@ -0,0 +1,564 @@
+use std::io;
+use std::io::Write;
+use std::io::BufWriter;
+use std::fs::File;
+use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+use cgmath::Vector3;
+use cgmath::Vector4;
+
+use game_data::Shape;
+use game_data::Line;
+use game_data::Message;
+use game_data::Label;
+use game_data::HeatScale;
+use colormap::Colormap;
+
+struct PaletteEntry {
+    symbol: String,
+    shape: Shape,
+    color: Vector4<f32>,
+    layer: u32,
+}
+
+/// Writes insight files, for use in bots.
+///
+/// The palette has to be declared before the first turn. Tiles and lines are collected until
+/// the turn is ended, which writes it out in one piece, so a viewer following the file never
+/// sees half a turn.
+///
+/// A disabled writer ignores all calls, so it can stay in release builds of a bot.
+pub struct InsightWriter {
+    output: Option<Box<dyn Write + Send>>,
+    num_rows: u32,
+    num_cols: u32,
+    background_color: Vector3<f32>,
+    palette: Vec<PaletteEntry>,
+    symbols: HashMap<String, usize>,
+    long_symbols: bool,
+    wrap: bool,
+    layer_names: BTreeMap<u32, String>,
+    cells: HashMap<usize, Vec<usize>>,
+    entities: BTreeMap<u32, (u32, u32, usize)>,
+    lines: Vec<Line>,
+    messages: Vec<Message>,
+    labels: Vec<Label>,
+    heat_scales: BTreeMap<u32, HeatScale>,
+    heat_maps: Vec<(u32, Vec<f32>)>,
+    num_turns: u32,
+}
+
+impl InsightWriter {
+    pub fn new<W>(output: W, num_rows: u32, num_cols: u32, background_color: Vector3<f32>)
+                  -> InsightWriter
+        where W: Write + Send + 'static
+    {
+        InsightWriter {
+            output: Some(Box::new(output)),
+            num_rows,
+            num_cols,
+            background_color,
+            palette: Vec::new(),
+            symbols: HashMap::new(),
+            long_symbols: false,
+            wrap: false,
+            layer_names: BTreeMap::new(),
+            cells: HashMap::new(),
+            entities: BTreeMap::new(),
+            lines: Vec::new(),
+            messages: Vec::new(),
+            labels: Vec::new(),
+            heat_scales: BTreeMap::new(),
+            heat_maps: Vec::new(),
+            num_turns: 0,
+        }
+    }
+
+    pub fn create(file_name: &str, num_rows: u32, num_cols: u32, background_color: Vector3<f32>)
+                  -> io::Result<InsightWriter> {
+        let file = File::create(file_name)?;
+        Ok(Self::new(BufWriter::new(file), num_rows, num_cols, background_color))
+    }
+
+    /// Creates a writer that does nothing.
+    pub fn disabled() -> InsightWriter {
+        InsightWriter {
+            output: None,
+            num_rows: 0,
+            num_cols: 0,
+            background_color: Vector3::new(0.0, 0.0, 0.0),
+            palette: Vec::new(),
+            symbols: HashMap::new(),
+            long_symbols: false,
+            wrap: false,
+            layer_names: BTreeMap::new(),
+            cells: HashMap::new(),
+            entities: BTreeMap::new(),
+            lines: Vec::new(),
+            messages: Vec::new(),
+            labels: Vec::new(),
+            heat_scales: BTreeMap::new(),
+            heat_maps: Vec::new(),
+            num_turns: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.output.is_some()
+    }
+
+    /// Declares how tiles with the given symbol are drawn.
+    ///
+    /// Symbols cannot contain whitespace or ',' and cannot be '.', which marks empty cells.
+    /// As soon as one symbol is longer than a single character, the file is written in the
+    /// format for long symbols.
+    pub fn add_palette_entry(&mut self, symbol: &str, shape: Shape, color: Vector4<f32>,
+                             layer: u32) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(self.num_turns == 0, "Palette entries have to be added before the first turn");
+        assert!(
+            !symbol.is_empty() && symbol != "." && !symbol.contains(|c: char| {
+                c.is_whitespace() || c == ','
+            }),
+            "Invalid palette symbol: {:?}",
+            symbol,
+        );
+        assert!(
+            ["turn", "layer", "heat", "symbols", "wrap"].iter().all(|&keyword| symbol != keyword),
+            "Palette symbol is a keyword: {:?}",
+            symbol,
+        );
+        assert!(!self.symbols.contains_key(symbol), "Duplicate palette symbol: {:?}", symbol);
+        if symbol.chars().count() > 1 {
+            self.long_symbols = true;
+        }
+        self.symbols.insert(symbol.to_string(), self.palette.len());
+        self.palette.push(PaletteEntry {
+            symbol: symbol.to_string(),
+            shape,
+            color,
+            layer,
+        });
+    }
+
+    /// Declares that the edges of the board wrap around, so lines take the shortest way around
+    /// the torus.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(self.num_turns == 0, "Wrapping has to be set before the first turn");
+        self.wrap = wrap;
+    }
+
+    /// Gives a layer a name to be shown in the viewer.
+    pub fn add_layer_name(&mut self, layer: u32, name: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(self.num_turns == 0, "Layer names have to be added before the first turn");
+        self.layer_names.insert(layer, name.to_string());
+    }
+
+    /// Sets the colormap of the heat maps of a layer, and optionally the values mapped to its
+    /// ends. Without a range, every heat map is scaled to its own values.
+    pub fn set_heat_scale(&mut self, layer: u32, colormap: Colormap, range: Option<(f32, f32)>) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(self.num_turns == 0, "Heat scales have to be set before the first turn");
+        if let Some((min, max)) = range {
+            assert!(min < max, "Heat scale range is empty: {} to {}", min, max);
+        }
+        self.heat_scales.insert(layer, HeatScale {
+            colormap,
+            range,
+        });
+    }
+
+    /// Adds a tile to the current turn. A cell can hold any number of tiles.
+    pub fn tile(&mut self, row: u32, col: u32, symbol: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(row < self.num_rows && col < self.num_cols);
+        let palette_index = match self.symbols.get(symbol) {
+            Some(&palette_index) => palette_index,
+            None => panic!("Unknown palette symbol: {:?}", symbol),
+        };
+        let position_index = (row * self.num_cols + col) as usize;
+        self.cells.entry(position_index).or_default().push(palette_index);
+    }
+
+    /// Adds an entity to the current turn, drawn as a tile. Entities keep their id from turn to
+    /// turn, so the viewer can tell where each of them went.
+    pub fn entity(&mut self, id: u32, row: u32, col: u32, symbol: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(row < self.num_rows && col < self.num_cols);
+        let palette_index = match self.symbols.get(symbol) {
+            Some(&palette_index) => palette_index,
+            None => panic!("Unknown palette symbol: {:?}", symbol),
+        };
+        if self.entities.insert(id, (row, col, palette_index)).is_some() {
+            panic!("Entity {} added twice in one turn", id);
+        }
+    }
+
+    /// Adds a line between the centers of two cells to the current turn.
+    pub fn line(&mut self, r1: u32, c1: u32, r2: u32, c2: u32, color: Vector4<f32>, layer: u32) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.lines.push(Line {
+            r1,
+            c1,
+            r2,
+            c2,
+            color,
+            layer,
+        });
+    }
+
+    /// Adds a message to the log of the current turn.
+    pub fn message(&mut self, text: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.messages.push(Message {
+            text: text.to_string(),
+            cell: None,
+            layer: None,
+        });
+    }
+
+    /// Adds a message about a cell to the current turn. It is hidden along with its layer.
+    pub fn cell_message(&mut self, row: u32, col: u32, layer: u32, text: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(row < self.num_rows && col < self.num_cols);
+        self.messages.push(Message {
+            text: text.to_string(),
+            cell: Some((row, col)),
+            layer: Some(layer),
+        });
+    }
+
+    /// Writes a short text onto a cell in the current turn, like a distance or an id.
+    pub fn label(&mut self, row: u32, col: u32, layer: u32, text: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(row < self.num_rows && col < self.num_cols);
+        self.labels.push(Label {
+            row,
+            col,
+            layer,
+            text: text.to_string(),
+        });
+    }
+
+    /// Adds a heat map to the current turn, with one value per cell row by row. Cells with a
+    /// NaN value are left empty.
+    pub fn heat_map(&mut self, layer: u32, values: &[f32]) {
+        if !self.is_enabled() {
+            return;
+        }
+        assert!(
+            values.len() == (self.num_rows * self.num_cols) as usize,
+            "A heat map needs one value per cell",
+        );
+        self.heat_maps.push((layer, values.to_vec()));
+    }
+
+    /// Writes the current turn and starts the next one.
+    pub fn end_turn(&mut self) -> io::Result<()> {
+        let output = match self.output {
+            Some(ref mut output) => output,
+            None => return Ok(()),
+        };
+        if self.num_turns == 0 {
+            writeln!(output, "{} {}", self.num_rows, self.num_cols)?;
+            let color = self.background_color;
+            writeln!(output, "{} {} {}", color.x, color.y, color.z)?;
+            if self.wrap {
+                writeln!(output, "wrap")?;
+            }
+            if self.long_symbols {
+                writeln!(output, "symbols long")?;
+            }
+            for entry in &self.palette {
+                let color = entry.color;
+                writeln!(
+                    output,
+                    "{} {} {} {} {} {} {}",
+                    entry.symbol,
+                    entry.shape.name(),
+                    color.x,
+                    color.y,
+                    color.z,
+                    color.w,
+                    entry.layer,
+                )?;
+            }
+            for (layer, name) in &self.layer_names {
+                writeln!(output, "layer {} {}", layer, quote(name))?;
+            }
+            for (layer, heat_scale) in &self.heat_scales {
+                write!(output, "heat {} {}", layer, heat_scale.colormap.name())?;
+                if let Some((min, max)) = heat_scale.range {
+                    write!(output, " {} {}", min, max)?;
+                }
+                writeln!(output)?;
+            }
+        }
+        writeln!(output, "turn")?;
+        let mut row = String::new();
+        for r in 0..self.num_rows {
+            row.clear();
+            for c in 0..self.num_cols {
+                if c != 0 {
+                    row.push(' ');
+                }
+                let palette_indices = match self.cells.get(&((r * self.num_cols + c) as usize)) {
+                    Some(palette_indices) => palette_indices,
+                    None => {
+                        row.push('.');
+                        continue;
+                    },
+                };
+                for (i, &palette_index) in palette_indices.iter().enumerate() {
+                    if self.long_symbols && i != 0 {
+                        row.push(',');
+                    }
+                    row.push_str(&self.palette[palette_index].symbol);
+                }
+            }
+            writeln!(output, "{}", row)?;
+        }
+        for (id, &(row, col, palette_index)) in &self.entities {
+            writeln!(output, "ent {} {} {} {}", id, row, col, self.palette[palette_index].symbol)?;
+        }
+        for line in &self.lines {
+            let color = line.color;
+            writeln!(
+                output,
+                "line {} {} {} {} {} {} {} {} {}",
+                line.r1,
+                line.c1,
+                line.r2,
+                line.c2,
+                color.x,
+                color.y,
+                color.z,
+                color.w,
+                line.layer,
+            )?;
+        }
+        for message in &self.messages {
+            match (message.cell, message.layer) {
+                (Some((row, col)), Some(layer)) => {
+                    writeln!(output, "msg {} {} {} {}", row, col, layer, quote(&message.text))?;
+                },
+                _ => writeln!(output, "msg {}", quote(&message.text))?,
+            }
+        }
+        for label in &self.labels {
+            writeln!(
+                output,
+                "label {} {} {} {}",
+                label.row,
+                label.col,
+                label.layer,
+                quote(&label.text),
+            )?;
+        }
+        for &(layer, ref values) in &self.heat_maps {
+            writeln!(output, "heat {}", layer)?;
+            let num_cols = self.num_cols as usize;
+            for r in 0..self.num_rows as usize {
+                let values = &values[r * num_cols..(r + 1) * num_cols];
+                row.clear();
+                for (c, value) in values.iter().enumerate() {
+                    if c != 0 {
+                        row.push(' ');
+                    }
+                    if value.is_nan() {
+                        row.push('.');
+                    } else {
+                        row.push_str(&value.to_string());
+                    }
+                }
+                writeln!(output, "{}", row)?;
+            }
+        }
+        output.flush()?;
+        self.cells.clear();
+        self.entities.clear();
+        self.lines.clear();
+        self.messages.clear();
+        self.labels.clear();
+        self.heat_maps.clear();
+        self.num_turns += 1;
+        Ok(())
+    }
+
+    /// Ends the file.
+    ///
+    /// A turn that has not been ended is dropped, but the file always gets at least one turn.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.num_turns == 0 {
+            self.cells.clear();
+            self.entities.clear();
+            self.lines.clear();
+            self.messages.clear();
+            self.labels.clear();
+            self.heat_maps.clear();
+            self.end_turn()?;
+        }
+        if let Some(ref mut output) = self.output {
+            writeln!(output, "end")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Puts a string in double quotes, escaping it the way the parser expects.
+fn quote(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::io;
+    use std::io::Cursor;
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use cgmath::Vector3;
+    use cgmath::Vector4;
+
+    use game_data::GameData;
+    use game_data::Shape;
+    use super::InsightWriter;
+
+    /// An output that can still be read after the writer has taken it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writes a game of the given size with a writer and loads it back. The writer is
+    /// finished after the function is done with it.
+    pub fn written<F>(num_rows: u32, num_cols: u32, write: F) -> GameData
+        where F: FnOnce(&mut InsightWriter)
+    {
+        let buffer = SharedBuffer::default();
+        let background_color = Vector3::new(0.1, 0.2, 0.3);
+        let mut writer = InsightWriter::new(buffer.clone(), num_rows, num_cols, background_color);
+        write(&mut writer);
+        writer.finish().unwrap();
+
+        let text = buffer.0.lock().unwrap().clone();
+        let mut game_data = GameData::from_reader(Cursor::new(text), "<buffer>").unwrap();
+        game_data.wait().unwrap();
+        game_data
+    }
+
+    #[test]
+    fn round_trip() {
+        let red = Vector4::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Vector4::new(0.0, 0.0, 1.0, 0.5);
+        let game_data = written(3, 4, |writer| {
+            writer.add_palette_entry("a", Shape::Circle, red, 1);
+            writer.add_palette_entry("hill", Shape::Triangle, blue, 2);
+            writer.set_wrap(true);
+            writer.add_layer_name(2, "hills");
+
+            writer.tile(0, 0, "a");
+            writer.tile(0, 0, "hill");
+            writer.tile(2, 3, "hill");
+            writer.entity(7, 1, 2, "a");
+            writer.line(0, 0, 2, 3, blue, 3);
+            writer.message("say \"hi\" \\ bye\nnext");
+            writer.cell_message(1, 1, 4, "near");
+            writer.label(2, 0, 5, "a \"b\"");
+            writer.end_turn().unwrap();
+            writer.entity(7, 1, 3, "a");
+            writer.end_turn().unwrap();
+        });
+        assert_eq!(game_data.num_turns(), 2);
+        assert_eq!((game_data.num_rows(), game_data.num_cols()), (3, 4));
+        assert!(game_data.wraps());
+        assert_eq!(game_data.layer_name(2), Some("hills"));
+
+        let mut tiles: Vec<_> = game_data.cell_tiles(0, 0, 0).into_iter()
+            .map(|(symbol, tile)| (symbol.to_string(), tile.shape, tile.layer, tile.entity))
+            .collect();
+        tiles.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(tiles, vec![
+            ("a".to_string(), Shape::Circle, 1, None),
+            ("hill".to_string(), Shape::Triangle, 2, None),
+        ]);
+        assert_eq!(game_data.cell_tiles(0, 2, 3).len(), 1);
+        let tile = game_data.cell_tiles(0, 0, 0).into_iter()
+            .find(|&(symbol, _)| symbol == "hill")
+            .unwrap()
+            .1;
+        assert_eq!(tile.color, blue);
+
+        let entities: Vec<_> = game_data.entities(0).iter()
+            .map(|tile| (tile.entity, tile.row, tile.col))
+            .collect();
+        assert_eq!(entities, vec![(Some(7), 1, 2)]);
+        assert_eq!(game_data.entity_position(7, 1).map(|waypoint| waypoint.col), Some(3));
+
+        let lines: Vec<_> = game_data.lines(0)
+            .map(|line| (line.r1, line.c1, line.r2, line.c2, line.color, line.layer))
+            .collect();
+        assert_eq!(lines, vec![(0, 0, 2, 3, blue, 3)]);
+
+        let messages: Vec<_> = game_data.messages(0)
+            .map(|message| (message.text, message.cell, message.layer))
+            .collect();
+        assert_eq!(messages, vec![
+            ("say \"hi\" \\ bye\nnext".to_string(), None, None),
+            ("near".to_string(), Some((1, 1)), Some(4)),
+        ]);
+
+        let labels: Vec<_> = game_data.labels(0)
+            .map(|label| (label.row, label.col, label.layer, label.text))
+            .collect();
+        assert_eq!(labels, vec![(2, 0, 5, "a \"b\"".to_string())]);
+        assert_eq!(game_data.lines(1).count(), 0);
+    }
+}